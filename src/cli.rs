@@ -9,6 +9,8 @@ use clap_verbosity_flag::ErrorLevel;
 use clap_verbosity_flag::TraceLevel;
 use serde::{Deserialize, Serialize};
 
+use crate::config::ConfigFormat;
+
 #[derive(Serialize, Deserialize, Clone, Debug, Parser)]
 #[command(
     version,
@@ -33,6 +35,10 @@ pub struct Cli {
     #[arg(hide = true, long)]
     pub ignore_project: bool,
 
+    /// Overwrite managed regions of generated files, even if they were edited by hand
+    #[arg(long, global = true)]
+    pub force: bool,
+
     #[command(subcommand)]
     pub operation: Operations,
 }
@@ -67,7 +73,7 @@ pub struct InitArgs {
     #[arg(short, long = "desc", default_value_t = String::from("Automatically generated config flake."))]
     pub description: String,
 
-    /// System architecture to build for
+    /// Default system architecture for new hosts (the devshell is available on every host's system too)
     #[arg(long, default_value_t = String::from("x86_64-linux"))]
     pub system: String,
 
@@ -84,11 +90,29 @@ pub struct InitArgs {
     pub comin_url: String,
 
     #[command(flatten)]
-    pub git: InitGitArgs
+    pub git: InitGitArgs,
+
+    /// Register an admin age key, generating one in sops' default key location if none exists.
+    /// If neither this nor --no-age-key is given, you'll be asked interactively.
+    #[arg(long, conflicts_with = "no_age_key")]
+    pub generate_age_key: bool,
+
+    /// Don't set up an admin age key
+    #[arg(long)]
+    pub no_age_key: bool,
+
+    /// Name to register the admin key under, or the current user's name if blank.
+    #[arg(long)]
+    pub admin_name: Option<String>,
+
+    /// File format to store the project's config in
+    #[arg(long, value_enum, default_value_t)]
+    pub config_format: ConfigFormat,
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, Default)]
 #[serde(rename_all = "lowercase")]
+#[allow(clippy::enum_variant_names)]
 pub enum Shell {
     #[default]
     Bash,
@@ -154,6 +178,702 @@ pub struct CompletionArgs {
 #[derive(Serialize, Deserialize, Clone, Debug, Args)]
 pub struct StatusArgs {}
 
+#[derive(Serialize, Deserialize, Clone, Debug, Args)]
+pub struct HostAddArgs {
+    /// Name of the new host
+    /// This is used as the host's hostname, flake attribute and directory name.
+    pub name: String,
+
+    /// System architecture of the host, or the project's default system if blank.
+    #[arg(long)]
+    pub system: Option<String>,
+
+    /// Tags to apply to the host (can be specified multiple times)
+    #[arg(short, long = "tag")]
+    pub tags: Vec<String>,
+
+    /// Age recipient the host decrypts secrets with
+    #[arg(long)]
+    pub age_key: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, Args)]
+pub struct HostRemoveArgs {
+    /// Name of the host to remove
+    pub name: String,
+
+    /// Also delete the host's directory (hosts/<name>)
+    #[arg(long)]
+    pub purge: bool,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, Args)]
+pub struct HostListArgs {}
+
+#[derive(Serialize, Deserialize, Clone, Debug, Args)]
+pub struct HostKeyImportArgs {
+    /// Name of the host
+    pub host: String,
+
+    /// Public key file or saved `ssh-keyscan` output containing the host's ed25519 key ("-" to read from stdin)
+    pub source: String,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, Args)]
+pub struct HostKeySetArgs {
+    /// Name of the host
+    pub host: String,
+
+    /// The host's age recipient (age1...)
+    pub recipient: String,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, Subcommand)]
+pub enum HostKeyOperations {
+    /// Derive the host's age recipient from its SSH ed25519 host key
+    Import(HostKeyImportArgs),
+
+    /// Set the host's age recipient directly
+    Set(HostKeySetArgs),
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, Args)]
+pub struct HostKeyArgs {
+    #[command(subcommand)]
+    pub operation: HostKeyOperations,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, Args)]
+pub struct HostTagArgs {
+    /// Name of the host
+    pub host: String,
+
+    /// Tags (groups) to apply to the host
+    #[arg(required = true)]
+    pub tags: Vec<String>,
+
+    /// Remove the tags instead of applying them
+    #[arg(short, long)]
+    pub remove: bool,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, Args)]
+pub struct HostTargetArgs {
+    /// Name of the host
+    pub host: String,
+
+    /// Hostname or IP address to deploy to over SSH
+    #[arg(required_unless_present = "clear")]
+    pub address: Option<String>,
+
+    /// SSH user, or the current user if blank
+    #[arg(short, long)]
+    pub user: Option<String>,
+
+    /// Activate the configuration with sudo (needed when the SSH user isn't root)
+    ///
+    /// sudo must not prompt for a password, unless deploying one host at a time with `--jobs 1`
+    #[arg(long)]
+    pub sudo: bool,
+
+    /// Build the configuration on the host instead of locally
+    #[arg(long)]
+    pub build_on_target: bool,
+
+    /// Remove the host's deploy target
+    #[arg(long, conflicts_with_all = ["address", "user", "sudo", "build_on_target"])]
+    pub clear: bool,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, Subcommand)]
+pub enum HostOperations {
+    /// Scaffold a new host and add it to the flake
+    Add(HostAddArgs),
+
+    /// Remove a host from the flake
+    Remove(HostRemoveArgs),
+
+    /// List all hosts in the project
+    List(HostListArgs),
+
+    /// Manage the age key a host decrypts secrets with
+    Key(HostKeyArgs),
+
+    /// Add a host to (or remove it from) groups
+    Tag(HostTagArgs),
+
+    /// Set how `nico deploy` reaches the host
+    Target(HostTargetArgs),
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, Args)]
+pub struct HostArgs {
+    #[command(subcommand)]
+    pub operation: HostOperations,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, Args)]
+pub struct FlakeAddArgs {
+    /// Input name of the flake (must be a valid nix identifier)
+    pub ident: String,
+
+    /// Flake URL (ie "github:owner/repo")
+    pub url: String,
+
+    /// Input that this flake's nixpkgs should follow
+    #[arg(long, default_value_t = String::from("nixpkgs"), conflicts_with = "no_follows")]
+    pub follows: String,
+
+    /// Don't make this flake's nixpkgs follow any other input
+    #[arg(long)]
+    pub no_follows: bool,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, Args)]
+pub struct FlakeRemoveArgs {
+    /// Input name of the flake to remove
+    pub ident: String,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, Args)]
+pub struct FlakeListArgs {}
+
+#[derive(Serialize, Deserialize, Clone, Debug, Subcommand)]
+pub enum FlakeOperations {
+    /// Add an extra flake input
+    Add(FlakeAddArgs),
+
+    /// Remove an extra flake input
+    Remove(FlakeRemoveArgs),
+
+    /// List all extra flake inputs
+    List(FlakeListArgs),
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, Args)]
+pub struct FlakeArgs {
+    #[command(subcommand)]
+    pub operation: FlakeOperations,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, Args)]
+pub struct DevshellAddArgs {
+    /// Attribute paths of the packages to add (ie "pkgs.jq" or "inputs.foo.packages.${system}.bar")
+    #[arg(required = true)]
+    pub packages: Vec<String>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, Args)]
+pub struct DevshellRemoveArgs {
+    /// Attribute paths of the packages to remove
+    #[arg(required = true)]
+    pub packages: Vec<String>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, Args)]
+pub struct DevshellListArgs {}
+
+#[derive(Serialize, Deserialize, Clone, Debug, Subcommand)]
+pub enum DevshellOperations {
+    /// Add packages to the project's devshell
+    Add(DevshellAddArgs),
+
+    /// Remove packages from the project's devshell
+    Remove(DevshellRemoveArgs),
+
+    /// List all extra devshell packages
+    List(DevshellListArgs),
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, Args)]
+pub struct DevshellArgs {
+    #[command(subcommand)]
+    pub operation: DevshellOperations,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, Args)]
+pub struct RemoteAddArgs {
+    /// Name of the remote
+    pub name: String,
+
+    /// URL that hosts will pull from
+    pub url: String,
+
+    /// Branch that hosts deploy from
+    #[arg(long, default_value_t = String::from("main"))]
+    pub main_branch: String,
+
+    /// Prefix of per-host testing branches (the host's name is appended)
+    #[arg(long, default_value_t = String::from("testing-"))]
+    pub testing_branch_prefix: String,
+
+    /// How often hosts poll the remote, in seconds
+    #[arg(long, default_value_t = 60)]
+    pub polling_period: u64,
+
+    /// Timeout for fetching from the remote, in seconds
+    #[arg(long, default_value_t = 300)]
+    pub timeout: u64,
+
+    /// Also add the remote to the project's git repository
+    #[arg(long)]
+    pub git: bool,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, Args)]
+pub struct RemoteRemoveArgs {
+    /// Name of the remote to remove
+    pub name: String,
+
+    /// Also remove the remote from the project's git repository
+    #[arg(long)]
+    pub git: bool,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, Args)]
+pub struct RemoteSetArgs {
+    /// Name of the remote to modify
+    pub name: String,
+
+    /// New URL of the remote
+    #[arg(long)]
+    pub url: Option<String>,
+
+    /// Branch that hosts deploy from
+    #[arg(long)]
+    pub main_branch: Option<String>,
+
+    /// Prefix of per-host testing branches (the host's name is appended)
+    #[arg(long)]
+    pub testing_branch_prefix: Option<String>,
+
+    /// How often hosts poll the remote, in seconds
+    #[arg(long)]
+    pub polling_period: Option<u64>,
+
+    /// Timeout for fetching from the remote, in seconds
+    #[arg(long)]
+    pub timeout: Option<u64>,
+
+    /// Also update the remote's URL in the project's git repository
+    #[arg(long)]
+    pub git: bool,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, Args)]
+pub struct RemoteListArgs {}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq, Default, ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum RemoteSource {
+    #[default]
+    Config,
+    Git,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, Args)]
+pub struct RemoteSyncArgs {
+    /// Which side wins when a remote exists in both but the URLs differ
+    #[arg(long, value_enum, default_value_t = RemoteSource::Config)]
+    pub prefer: RemoteSource,
+
+    /// Only show what would change
+    #[arg(long)]
+    pub dry_run: bool,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, Subcommand)]
+pub enum RemoteOperations {
+    /// Add a remote for hosts to pull from
+    Add(RemoteAddArgs),
+
+    /// Remove a remote
+    Remove(RemoteRemoveArgs),
+
+    /// Modify an existing remote
+    Set(RemoteSetArgs),
+
+    /// List all configured remotes
+    List(RemoteListArgs),
+
+    /// Reconcile the configured remotes with the git repository's remotes
+    Sync(RemoteSyncArgs),
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, Args)]
+pub struct RemoteArgs {
+    #[command(subcommand)]
+    pub operation: RemoteOperations,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, Args)]
+pub struct SecretsAdminAddArgs {
+    /// Name of the admin
+    pub name: String,
+
+    /// The admin's age recipient (age1...)
+    pub recipient: String,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, Args)]
+pub struct SecretsAdminRemoveArgs {
+    /// Name of the admin to remove
+    pub name: String,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, Args)]
+pub struct SecretsAdminListArgs {}
+
+#[derive(Serialize, Deserialize, Clone, Debug, Subcommand)]
+pub enum SecretsAdminOperations {
+    /// Add an admin, who can decrypt every secret
+    Add(SecretsAdminAddArgs),
+
+    /// Remove an admin
+    Remove(SecretsAdminRemoveArgs),
+
+    /// List all admins
+    List(SecretsAdminListArgs),
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, Args)]
+pub struct SecretsAdminArgs {
+    #[command(subcommand)]
+    pub operation: SecretsAdminOperations,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, Args)]
+pub struct SecretsRekeyArgs {
+    /// Only show which secrets would be re-encrypted
+    #[arg(long)]
+    pub dry_run: bool,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, Args)]
+pub struct SecretsAddArgs {
+    /// Host the secret belongs to, or @<tag> for a group of hosts
+    pub scope: String,
+
+    /// Name of the secret
+    pub name: String,
+
+    /// Value of the secret. If blank, it's read from a prompt (or stdin if it isn't a terminal)
+    #[arg(long)]
+    pub value: Option<String>,
+
+    /// User that owns the decrypted secret on the host
+    #[arg(long)]
+    pub owner: Option<String>,
+
+    /// Group that owns the decrypted secret on the host
+    #[arg(long)]
+    pub group: Option<String>,
+
+    /// Octal permissions of the decrypted secret on the host (eg. 0440)
+    #[arg(long)]
+    pub mode: Option<String>,
+
+    /// Systemd unit to restart when the secret changes. Can be given multiple times
+    #[arg(long = "restart-unit")]
+    pub restart_units: Vec<String>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, Args)]
+pub struct SecretsEditArgs {
+    /// Host the secret belongs to, or @<tag> for a group of hosts
+    pub scope: String,
+
+    /// Name of the secret
+    pub name: String,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, Args)]
+pub struct SecretsSetArgs {
+    /// Host the secret belongs to, or @<tag> for a group of hosts
+    pub scope: String,
+
+    /// Name of the secret
+    pub name: String,
+
+    /// User that owns the decrypted secret on the host
+    #[arg(long)]
+    pub owner: Option<String>,
+
+    /// Group that owns the decrypted secret on the host
+    #[arg(long)]
+    pub group: Option<String>,
+
+    /// Octal permissions of the decrypted secret on the host (eg. 0440)
+    #[arg(long)]
+    pub mode: Option<String>,
+
+    /// Systemd unit to restart when the secret changes. Replaces the existing units, can be given multiple times
+    #[arg(long = "restart-unit")]
+    pub restart_units: Vec<String>,
+
+    /// Remove all restart units
+    #[arg(long, conflicts_with = "restart_units")]
+    pub clear_restart_units: bool,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, Args)]
+pub struct SecretsGetArgs {
+    /// Host the secret belongs to, or @<tag> for a group of hosts
+    pub scope: String,
+
+    /// Name of the secret
+    pub name: String,
+
+    /// Key to extract from the secret file, or the secret's name if blank
+    #[arg(short, long)]
+    pub key: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, Args)]
+pub struct SecretsAuditArgs {
+    /// Print the report as JSON
+    #[arg(long)]
+    pub json: bool,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, Subcommand)]
+pub enum SecretsOperations {
+    /// Create a new encrypted secret for a host or group
+    Add(SecretsAddArgs),
+
+    /// Open an existing secret in $EDITOR through sops
+    Edit(SecretsEditArgs),
+
+    /// Decrypt a single value from a secret and print it
+    Get(SecretsGetArgs),
+
+    /// Change how a secret is deployed on its hosts (owner, mode, etc)
+    Set(SecretsSetArgs),
+
+    /// Manage admin recipients
+    Admin(SecretsAdminArgs),
+
+    /// Re-encrypt every secret whose recipients no longer match the config, and commit the result
+    Rekey(SecretsRekeyArgs),
+
+    /// Report secrets that can't be read by their hosts, are shared too widely, or aren't used by any host
+    Audit(SecretsAuditArgs),
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, Args)]
+pub struct SecretsArgs {
+    #[command(subcommand)]
+    pub operation: SecretsOperations,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, Args)]
+pub struct GroupAddArgs {
+    /// Name of the new group, which is also the tag hosts are added to it with
+    pub name: String,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, Args)]
+pub struct GroupRemoveArgs {
+    /// Name of the group to remove. It's also removed from every host's tags
+    pub name: String,
+
+    /// Also delete the group's module (modules/groups/<name>.nix)
+    #[arg(long)]
+    pub purge: bool,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, Args)]
+pub struct GroupListArgs {}
+
+#[derive(Serialize, Deserialize, Clone, Debug, Subcommand)]
+pub enum GroupOperations {
+    /// Create a group & scaffold its shared module
+    Add(GroupAddArgs),
+
+    /// Remove a group
+    Remove(GroupRemoveArgs),
+
+    /// List all groups & their hosts
+    List(GroupListArgs),
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, Args)]
+pub struct GroupArgs {
+    #[command(subcommand)]
+    pub operation: GroupOperations,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, Args)]
+pub struct BuildArgs {
+    /// Hosts to build, or @<tag> for every host in a group (defaults to every host)
+    pub targets: Vec<String>,
+
+    /// Print the summary as JSON
+    #[arg(long)]
+    pub json: bool,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, Args)]
+pub struct DiffArgs {
+    /// Name of the host
+    pub host: String,
+
+    /// Git revision to compare the working tree against
+    #[arg(short, long, default_value = "HEAD")]
+    pub against: String,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, Default, ValueEnum)]
+#[serde(rename_all = "kebab-case")]
+#[clap(rename_all = "kebab-case")]
+pub enum DeployAction {
+    /// Activate the new configuration & make it the boot default
+    #[default]
+    Switch,
+
+    /// Make the new configuration the boot default, without activating it
+    Boot,
+
+    /// Activate the new configuration, without making it the boot default
+    Test,
+
+    /// Show what activating the new configuration would change
+    DryActivate,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, Args)]
+pub struct DeployArgs {
+    /// Hosts to deploy, or @<tag> for every host in a group
+    #[arg(required = true)]
+    pub targets: Vec<String>,
+
+    /// What nixos-rebuild should do with the new configuration
+    #[arg(short, long, value_enum, default_value_t)]
+    pub action: DeployAction,
+
+    /// Maximum number of hosts to deploy at once. With 1, nixos-rebuild runs in the foreground, so it can prompt
+    /// for sudo & SSH passwords
+    #[arg(short, long, default_value_t = 4)]
+    pub jobs: usize,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, Args)]
+pub struct RegenArgs {
+    /// Don't write anything, just fail if any generated file is out of date (for CI)
+    #[arg(long)]
+    pub check: bool,
+
+    /// Write the changes without asking for confirmation
+    #[arg(short, long, conflicts_with = "check")]
+    pub yes: bool,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, Args)]
+pub struct TemplatesListArgs {}
+
+#[derive(Serialize, Deserialize, Clone, Debug, Args)]
+pub struct TemplatesEjectArgs {
+    /// Key of the template to eject (its path minus `.template`, eg. flake/root.nix)
+    pub key: String,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, Subcommand)]
+pub enum TemplatesOperations {
+    /// List built-in templates & the project's overrides
+    List(TemplatesListArgs),
+
+    /// Copy a built-in template into .nico/templates/ so it can be customized
+    Eject(TemplatesEjectArgs),
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, Args)]
+pub struct TemplatesArgs {
+    #[command(subcommand)]
+    pub operation: TemplatesOperations,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, Args)]
+pub struct ConfigMigrateArgs {}
+
+#[derive(Serialize, Deserialize, Clone, Debug, Args)]
+pub struct ConfigConvertArgs {
+    /// Format to convert the config to
+    #[arg(long, value_enum)]
+    pub to: ConfigFormat,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, Args)]
+pub struct ConfigGetArgs {
+    /// Dotted path of the value (eg. resources.remotes.origin.polling_period), or the whole config if blank
+    pub path: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, Args)]
+pub struct ConfigWriteArgs {
+    /// Regenerate the project's files after changing the config
+    #[arg(long)]
+    pub regen: bool,
+
+    /// Commit the change (& any regenerated files)
+    #[arg(long)]
+    pub commit: bool,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, Args)]
+pub struct ConfigSetArgs {
+    /// Dotted path of the value to set (eg. resources.remotes.origin.polling_period)
+    pub path: String,
+
+    /// New value. Parsed as JSON if possible (eg. 30, true, ["a"]), otherwise used as a string
+    pub value: String,
+
+    /// Always treat the value as a string
+    #[arg(short, long)]
+    pub string: bool,
+
+    #[command(flatten)]
+    pub write: ConfigWriteArgs,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, Args)]
+pub struct ConfigUnsetArgs {
+    /// Dotted path of the value to remove
+    pub path: String,
+
+    #[command(flatten)]
+    pub write: ConfigWriteArgs,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, Args)]
+pub struct ConfigSchemaArgs {}
+
+#[derive(Serialize, Deserialize, Clone, Debug, Subcommand)]
+pub enum ConfigOperations {
+    /// Print a value from the config
+    Get(ConfigGetArgs),
+
+    /// Change a value in the config
+    Set(ConfigSetArgs),
+
+    /// Remove a value from the config
+    Unset(ConfigUnsetArgs),
+
+    /// Upgrade the config to the current format, and commit the result
+    Migrate(ConfigMigrateArgs),
+
+    /// Convert the config to a different file format
+    Convert(ConfigConvertArgs),
+
+    /// Print the config's JSON schema
+    Schema(ConfigSchemaArgs),
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, Args)]
+pub struct ConfigArgs {
+    #[command(subcommand)]
+    pub operation: ConfigOperations,
+}
+
 #[derive(Serialize, Deserialize, Clone, Debug, Subcommand)]
 pub enum Operations {
     /// Initializes a new configuration directory
@@ -163,5 +883,41 @@ pub enum Operations {
     Completions(CompletionArgs),
 
     /// Get project status
-    Status(StatusArgs)
+    Status(StatusArgs),
+
+    /// Manage the project's hosts
+    Host(HostArgs),
+
+    /// Manage groups of hosts that share configuration
+    Group(GroupArgs),
+
+    /// Manage extra flake inputs
+    Flake(FlakeArgs),
+
+    /// Manage packages available in the project's devshell
+    Devshell(DevshellArgs),
+
+    /// Manage the git remotes that hosts pull their configuration from
+    Remote(RemoteArgs),
+
+    /// Manage SOPS secrets & their recipients
+    Secrets(SecretsArgs),
+
+    /// Build hosts' system configurations locally
+    Build(BuildArgs),
+
+    /// Build & activate hosts' configurations over SSH
+    Deploy(DeployArgs),
+
+    /// Show how a host's packages change between a git revision & the working tree
+    Diff(DiffArgs),
+
+    /// Re-render every generated file from the config, showing a diff first
+    Regen(RegenArgs),
+
+    /// Manage the project's template overrides
+    Templates(TemplatesArgs),
+
+    /// Inspect & maintain the project's config file
+    Config(ConfigArgs),
 }
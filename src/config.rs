@@ -1,16 +1,19 @@
 #![allow(dead_code)]
 
 use std::{
-    collections::HashMap, fs, path::{Path, PathBuf}
+    collections::{BTreeMap, BTreeSet, HashMap}, fmt::Display, fs, path::{Path, PathBuf}, str::FromStr
 };
 
 use bon::Builder;
+use schemars::JsonSchema;
+use clap::ValueEnum;
 use serde::{Deserialize, Serialize};
 use serde_json::json;
+use log::*;
 
-use crate::{cli::InitArgs, context::Context};
+use crate::{cli::InitArgs, context::Context, nix, regions::{self, Legacy}};
 
-#[derive(Serialize, Deserialize, Clone, Debug, Builder)]
+#[derive(Serialize, Deserialize, Clone, Debug, Builder, JsonSchema)]
 pub struct GitRemote {
     #[builder(start_fn, into)]
     pub name: String,
@@ -18,33 +21,58 @@ pub struct GitRemote {
     #[builder(start_fn, into)]
     pub url: String,
 
-    #[builder(default = "main".to_string(), into)]
+    #[builder(default = GitRemote::default_main_branch(), into)]
+    #[serde(default = "GitRemote::default_main_branch")]
     pub main_branch: String,
 
-    #[builder(default = "testing-".to_string(), into)]
+    #[builder(default = GitRemote::default_testing_branch_prefix(), into)]
+    #[serde(default = "GitRemote::default_testing_branch_prefix")]
     pub testing_branch_prefix: String,
 
-    #[builder(default = 60)]
+    /// Seconds between polls of the remote
+    #[builder(default = GitRemote::default_polling_period())]
+    #[serde(default = "GitRemote::default_polling_period")]
     pub polling_period: u64,
 
-    #[builder(default = 300)]
+    /// Seconds before a fetch of the remote times out
+    #[builder(default = GitRemote::default_timeout())]
+    #[serde(default = "GitRemote::default_timeout")]
     pub timeout: u64
 }
 
 impl GitRemote {
+    fn default_main_branch() -> String {
+        String::from("main")
+    }
+
+    fn default_testing_branch_prefix() -> String {
+        String::from("testing-")
+    }
+
+    fn default_polling_period() -> u64 {
+        60
+    }
+
+    fn default_timeout() -> u64 {
+        300
+    }
+
     pub fn as_nix(&self) -> String {
-        handlebars::Handlebars::new().render_template("{
-            name = \"{{name}}\";
-            url = \"{{url}}\";
-            branches.main.name = \"{{main_branch}}\";
-            branches.testing.name = \"{{testing_branch_prefix}}${config.services.comin.hostname}\";
-            poller.period = {{polling_period}};
-            timeout = {{timeout}};
-        }", &self).expect("Failed to render remote into nix config.")
+        let mut templater = handlebars::Handlebars::new();
+        templater.register_escape_fn(handlebars::no_escape);
+        nix::register_helpers(&mut templater);
+        templater.render_template(r#"{
+  name = {{nix-string name}};
+  url = {{nix-string url}};
+  branches.main.name = {{nix-string main_branch}};
+  branches.testing.name = {{nix-string testing_branch_prefix}} + config.services.comin.hostname;
+  poller.period = {{polling_period}};
+  timeout = {{timeout}};
+}"#, &self).expect("Failed to render remote into nix config.")
     }
 }
 
-#[derive(Serialize, Deserialize, Clone, Debug)]
+#[derive(Serialize, Deserialize, Clone, Debug, JsonSchema)]
 pub struct InitConfig {
     pub description: String,
     pub nix: String,
@@ -53,31 +81,367 @@ pub struct InitConfig {
     pub comin_url: String
 }
 
-#[derive(Serialize, Deserialize, Clone, Debug)]
+/// Inputs that are always present in the generated flake, and can't be redefined as extra flakes.
+pub const RESERVED_INPUTS: [&str; 4] = ["self", "nixpkgs", "sops-nix", "comin"];
+
+/// Nix keywords, which can't be used as plain identifiers.
+pub const NIX_KEYWORDS: [&str; 10] = ["assert", "else", "if", "in", "inherit", "let", "or", "rec", "then", "with"];
+
+/// Checks whether a string is a plain (unquoted) nix identifier.
+pub fn valid_ident(ident: impl AsRef<str>) -> bool {
+    let ident = ident.as_ref();
+    ident.starts_with(|c: char| c.is_ascii_alphabetic() || c == '_')
+        && ident.chars().all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-' || c == '\'')
+        && !NIX_KEYWORDS.contains(&ident)
+}
+
+/// Splits a nix attribute path (ie `pkgs.jq` or `inputs.foo.packages.${system}.bar`) into its segments.
+/// Segments may be identifiers, quoted strings or `${ident}` interpolations. Returns `None` if the path is malformed.
+pub fn parse_attr_path(path: impl AsRef<str>) -> Option<Vec<String>> {
+    let mut segments = vec![];
+    let mut rest = path.as_ref();
+    loop {
+        let end = if rest.starts_with("${") {
+            let end = rest.find('}')? + 1;
+            valid_ident(&rest[2..end - 1]).then_some(end)?
+        } else if let Some(quoted) = rest.strip_prefix('"') {
+            let end = quoted.find('"')? + 2;
+            (!rest[..end].contains("${") && !rest[..end].contains('\\')).then_some(end)?
+        } else {
+            let end = rest.find('.').unwrap_or(rest.len());
+            valid_ident(&rest[..end]).then_some(end)?
+        };
+
+        segments.push(rest[..end].to_string());
+        rest = &rest[end..];
+        if rest.is_empty() {
+            return Some(segments);
+        }
+        rest = rest.strip_prefix('.')?;
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, JsonSchema)]
 pub struct ExtraFlake {
     pub ident: String,
     pub url: String,
-    pub follows: String,
+
+    /// Input that this flake's nixpkgs input should follow, if any
+    #[serde(default)]
+    pub follows: Option<String>,
 }
 
-#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+#[derive(Serialize, Deserialize, Clone, Debug, Default, JsonSchema)]
 pub struct Resources {
     pub extra_flakes: Vec<ExtraFlake>,
     pub dev_packages: Vec<String>,
     pub remotes: HashMap<String, GitRemote>
 }
 
-#[derive(Serialize, Deserialize, Clone, Debug)]
+/// How `nico deploy` reaches a host.
+#[derive(Serialize, Deserialize, Clone, Debug, JsonSchema)]
+pub struct DeployTarget {
+    /// Hostname or IP address to deploy to over SSH
+    pub address: String,
+
+    /// SSH user, or the current user if blank
+    #[serde(default)]
+    pub user: Option<String>,
+
+    /// Activate the configuration with sudo (needed when the SSH user isn't root)
+    ///
+    /// sudo must not prompt for a password, unless deploying one host at a time with `--jobs 1`
+    #[serde(default)]
+    pub sudo: bool,
+
+    /// Build the configuration on the host instead of locally
+    #[serde(default)]
+    pub build_on_target: bool,
+}
+
+impl DeployTarget {
+    /// The `[user@]address` SSH destination.
+    pub fn destination(&self) -> String {
+        match &self.user {
+            Some(user) => format!("{user}@{}", self.address),
+            None => self.address.clone(),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, JsonSchema)]
+pub struct Host {
+    pub name: String,
+    pub system: String,
+
+    #[serde(default)]
+    pub tags: Vec<String>,
+
+    /// Age recipient the host decrypts its secrets with
+    #[serde(default)]
+    pub age_key: Option<String>,
+
+    #[serde(default)]
+    pub deploy: Option<DeployTarget>,
+}
+
+impl Host {
+    /// Host names double as flake attributes, hostnames & directory names, so they're restricted to a safe subset.
+    pub fn valid_name(name: impl AsRef<str>) -> bool {
+        let name = name.as_ref();
+        name.starts_with(|c: char| c.is_ascii_alphabetic())
+            && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+    }
+
+    pub fn directory(&self) -> PathBuf {
+        PathBuf::from("hosts").join(&self.name)
+    }
+}
+
+/// Which hosts a secret is shared with. Groups are written as `@tag` on the command line.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash, JsonSchema)]
+#[serde(tag = "kind", content = "name", rename_all = "lowercase")]
+pub enum SecretScope {
+    Host(String),
+    Group(String),
+}
+
+impl SecretScope {
+    pub fn parse(scope: impl AsRef<str>) -> Self {
+        let scope = scope.as_ref();
+        match scope.strip_prefix('@') {
+            Some(group) => Self::Group(group.to_string()),
+            None => Self::Host(scope.to_string()),
+        }
+    }
+
+    pub fn name(&self) -> &str {
+        match self {
+            Self::Host(name) | Self::Group(name) => name,
+        }
+    }
+
+    /// Directory (relative to the project root) that this scope's secret files live in
+    pub fn directory(&self) -> PathBuf {
+        match self {
+            Self::Host(host) => PathBuf::from("secrets/hosts").join(host),
+            Self::Group(group) => PathBuf::from("secrets/groups").join(group),
+        }
+    }
+}
+
+impl Display for SecretScope {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Host(host) => write!(f, "{host}"),
+            Self::Group(group) => write!(f, "@{group}"),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, JsonSchema)]
+pub struct Secret {
+    pub name: String,
+    pub scope: SecretScope,
+
+    /// User that owns the decrypted file on the host (sops-nix defaults to root)
+    #[serde(default)]
+    pub owner: Option<String>,
+
+    /// Group that owns the decrypted file on the host
+    #[serde(default)]
+    pub group: Option<String>,
+
+    /// Octal permissions of the decrypted file on the host (sops-nix defaults to 0400)
+    #[serde(default)]
+    pub mode: Option<String>,
+
+    /// Systemd units to restart when the secret changes
+    #[serde(default)]
+    pub restart_units: Vec<String>,
+}
+
+impl Secret {
+    pub fn new(name: impl Into<String>, scope: SecretScope) -> Self {
+        Self {
+            name: name.into(),
+            scope,
+            owner: None,
+            group: None,
+            mode: None,
+            restart_units: vec![],
+        }
+    }
+
+    pub fn path(&self) -> PathBuf {
+        self.scope.directory().join(format!("{}.yaml", self.name))
+    }
+
+    /// Checks that a mode is a 3 or 4 digit octal string, like `0440`.
+    pub fn valid_mode(mode: impl AsRef<str>) -> bool {
+        let mode = mode.as_ref();
+        (3..=4).contains(&mode.len()) && mode.chars().all(|c| ('0'..='7').contains(&c))
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, Default, JsonSchema)]
+pub struct Secrets {
+    /// Age recipients of admins, by name. Admins can decrypt every secret.
+    pub admins: BTreeMap<String, String>,
+    pub files: Vec<Secret>,
+}
+
+/// Checks whether a string is a valid age X25519 recipient (`age1...`).
+pub fn valid_recipient(recipient: impl AsRef<str>) -> bool {
+    age::x25519::Recipient::from_str(recipient.as_ref()).is_ok()
+}
+
+/// File formats the config can be stored in.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq, Default, ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum ConfigFormat {
+    #[default]
+    Json,
+    Yaml,
+    Toml,
+}
+
+impl ConfigFormat {
+    pub const ALL: [Self; 3] = [Self::Json, Self::Yaml, Self::Toml];
+
+    pub fn file_name(&self) -> &'static str {
+        match self {
+            Self::Json => "nico.config.json",
+            Self::Yaml => "nico.config.yaml",
+            Self::Toml => "nico.config.toml",
+        }
+    }
+
+    /// Finds the config file in a directory, warning if there's more than one.
+    pub fn detect(directory: impl AsRef<Path>) -> Option<Self> {
+        let found: Vec<Self> = Self::ALL
+            .into_iter()
+            .filter(|f| directory.as_ref().join(f.file_name()).is_file())
+            .collect();
+        if found.len() > 1 {
+            warn!("Found multiple config files, using {}.", found[0].file_name());
+        }
+        found.first().copied()
+    }
+
+    pub fn parse(&self, raw: &str) -> crate::Result<serde_json::Value> {
+        Ok(match self {
+            Self::Json => serde_json::from_str(raw)?,
+            Self::Yaml => serde_norway::from_str(raw)?,
+            Self::Toml => serde_json::to_value(toml::from_str::<toml::Value>(raw)?)?,
+        })
+    }
+
+    pub fn serialize(&self, value: &impl Serialize) -> crate::Result<String> {
+        Ok(match self {
+            Self::Json => serde_json::to_string_pretty(value)?,
+            Self::Yaml => serde_norway::to_string(value)?,
+            Self::Toml => toml::to_string_pretty(value)?,
+        })
+    }
+}
+
+/// Where the config's JSON schema is written, relative to the project root.
+pub const SCHEMA_PATH: &str = ".nico/config.schema.json";
+
+/// Version of the config format written by this build of nico.
+pub const CONFIG_VERSION: u32 = 2;
+
+/// Upgrades a config from the version at its index to the next one.
+/// Configs written before versioning was introduced don't have a `version` field, and are treated as version 0.
+const MIGRATIONS: [fn(&mut serde_json::Map<String, serde_json::Value>); CONFIG_VERSION as usize] = [
+    // 0 -> 1: fill in the sections that used to be optional
+    |config| {
+        config.entry("hosts").or_insert(json!({}));
+        config.entry("secrets").or_insert(json!({"admins": {}, "files": []}));
+    },
+    // 1 -> 2: groups are declared explicitly, starting with every tag that's already applied to a host
+    |config| {
+        let tags: BTreeSet<String> = config["hosts"]
+            .as_object()
+            .into_iter()
+            .flat_map(|hosts| hosts.values())
+            .flat_map(|host| host["tags"].as_array().cloned().unwrap_or_default())
+            .filter_map(|tag| tag.as_str().map(String::from))
+            .collect();
+        config.insert(String::from("groups"), json!(tags));
+    },
+];
+
+/// Parts of `flake.nix` that older templates generated outside of managed regions.
+pub(crate) const LEGACY_FLAKE: [Legacy; 1] = [
+    // Before devShells were generated for every system
+    Legacy {
+        region: "devshells",
+        text: r#"
+      devShells.${system}.default = pkgs.mkShell {
+        packages = [
+          pkgs.age
+          pkgs.sops
+          # nico:begin dev-packages
+          # nico:end
+        ];
+
+        shellHook = if nico-override-env == "" then ''
+          flake_store_root="${builtins.toString ./.}"
+          if repo_root="$( \
+            2>/dev/null git rev-parse --show-toplevel \
+          )"; then
+            flake_root="$repo_root"
+          else
+            flake_root="$flake_store_root"
+          fi
+          export NICO_ENV="$flake_root"
+        '' else ''
+          export NICO_ENV="${nico-override-env}"
+        '';
+      };
+"#,
+    },
+];
+
+/// Path (relative to the project root) of the module shared by every host in a group.
+pub fn group_module(group: impl AsRef<str>) -> PathBuf {
+    PathBuf::from("modules/groups").join(format!("{}.nix", group.as_ref()))
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, JsonSchema)]
 pub struct Configuration {
+    #[serde(default)]
+    pub version: u32,
+
+    /// Format the config was loaded from, & will be saved in
+    #[serde(skip)]
+    pub format: ConfigFormat,
+
     pub init: InitConfig,
     pub resources: Resources,
+
+    #[serde(default)]
+    pub hosts: BTreeMap<String, Host>,
+
+    /// Host groups, each with a shared module that's imported by every host tagged with the group's name
+    #[serde(default)]
+    pub groups: BTreeSet<String>,
+
+    #[serde(default)]
+    pub secrets: Secrets,
 }
 
 impl Configuration {
     pub fn new(root: PathBuf, init: InitArgs, remotes: Vec<GitRemote>) -> crate::Result<Self> {
-        let mut resources = Resources::default();
-        resources.remotes = remotes.into_iter().map(|r| (r.name.clone(), r)).collect();
+        let resources = Resources {
+            remotes: remotes.into_iter().map(|r| (r.name.clone(), r)).collect(),
+            ..Default::default()
+        };
         let new_config = Self {
+            version: CONFIG_VERSION,
+            format: init.config_format,
             init: InitConfig {
                 description: init.description.clone(),
                 nix: init.nix.clone(),
@@ -85,7 +449,10 @@ impl Configuration {
                 sops_url: init.sops_url.clone(),
                 comin_url: init.comin_url.clone(),
             },
-            resources: resources,
+            resources,
+            hosts: BTreeMap::new(),
+            groups: BTreeSet::new(),
+            secrets: Secrets::default(),
         };
         new_config.save(root)?;
         Ok(new_config)
@@ -94,9 +461,8 @@ impl Configuration {
     pub fn load() -> crate::Result<Self> {
         let mut current = std::env::current_dir()?;
         loop {
-            if current.join("nico.config.json").exists() {
-                let raw_config = fs::read_to_string(current.join("nico.config.json"))?;
-                return Ok(serde_json::from_str::<Self>(&raw_config)?);
+            if ConfigFormat::detect(&current).is_some() {
+                return Self::load_path(current);
             } else if let Some(parent) = current.parent() {
                 current = parent.to_path_buf();
             } else {
@@ -105,44 +471,356 @@ impl Configuration {
         }
     }
 
-    pub fn load_path(path: impl AsRef<Path>) -> crate::Result<Self> {
+    /// Resolves a project directory or config file into the config file's path & format.
+    fn config_path(path: impl AsRef<Path>) -> crate::Result<(PathBuf, ConfigFormat)> {
         let path = path.as_ref();
-        let config_path = (if path.is_dir() && path.join("nico.config.json").exists() {
-            Ok(path.join("nico.config.json"))
-        }
-        else if path.is_file() && path.ends_with("nico.config.json") {
-            Ok(path.to_path_buf())
+        let found = if path.is_dir() {
+            ConfigFormat::detect(path).map(|format| (path.join(format.file_name()), format))
+        } else if path.is_file() {
+            ConfigFormat::ALL
+                .into_iter()
+                .find(|f| path.ends_with(f.file_name()))
+                .map(|format| (path.to_path_buf(), format))
         } else {
-            Err(crate::Error::ConfigNotFound)
-        })?.canonicalize()?;
-        let deserialized = serde_json::from_str::<Self>(&fs::read_to_string(config_path.clone())?)?;
-        Ok(deserialized)
+            None
+        };
+
+        let (path, format) = found.ok_or(crate::Error::ConfigNotFound)?;
+        Ok((path.canonicalize()?, format))
+    }
+
+    /// Reads the raw config at a path, without migrating it.
+    fn read_raw(path: impl AsRef<Path>) -> crate::Result<(serde_json::Value, ConfigFormat)> {
+        let (path, format) = Self::config_path(path)?;
+        Ok((format.parse(&fs::read_to_string(path)?)?, format))
+    }
+
+    /// Version of the config file at a path, as it is on disk.
+    pub fn file_version(path: impl AsRef<Path>) -> crate::Result<u32> {
+        Ok(Self::read_raw(path)?
+            .0
+            .get("version")
+            .and_then(|v| v.as_u64())
+            .unwrap_or(0) as u32)
+    }
+
+    /// Upgrades a raw config to [`CONFIG_VERSION`], failing if it was written by a newer version of nico.
+    pub fn migrate(mut raw: serde_json::Value) -> crate::Result<Self> {
+        let Some(config) = raw.as_object_mut() else {
+            return Err(crate::Error::ConfigNotAMap);
+        };
+        let version = config.get("version").and_then(|v| v.as_u64()).unwrap_or(0) as u32;
+        if version > CONFIG_VERSION {
+            return Err(crate::Error::ConfigTooNew(version, CONFIG_VERSION));
+        }
+
+        for (from, migration) in MIGRATIONS.iter().enumerate().skip(version as usize) {
+            debug!("Migrating config from version {from} to {}", from + 1);
+            migration(config);
+        }
+        config.insert(String::from("version"), json!(CONFIG_VERSION));
+        Ok(serde_json::from_value(raw)?)
+    }
+
+    pub fn load_path(path: impl AsRef<Path>) -> crate::Result<Self> {
+        let (raw, format) = Self::read_raw(&path)?;
+        if raw.get("version").and_then(|v| v.as_u64()).unwrap_or(0) < CONFIG_VERSION as u64 {
+            info!("The config uses an older format, run `nico config migrate` to upgrade it.");
+        }
+        Ok(Self {
+            format,
+            ..Self::migrate(raw)?
+        })
+    }
+
+    /// JSON schema describing the config file.
+    pub fn schema() -> crate::Result<String> {
+        Ok(serde_json::to_string_pretty(&schemars::schema_for!(Configuration))?)
     }
 
     pub fn save(&self, directory: impl AsRef<Path>) -> crate::Result<()> {
-        let serialized = serde_json::to_string_pretty(&self)?;
-        fs::write(directory.as_ref().join("nico.config.json"), serialized)?;
+        fs::write(directory.as_ref().join(self.format.file_name()), self.serialize()?)?;
         Ok(())
     }
 
+    /// The config file's content, as [`Configuration::save`] writes it.
+    pub fn serialize(&self) -> crate::Result<String> {
+        #[derive(Serialize)]
+        struct WithSchema<'a> {
+            #[serde(rename = "$schema")]
+            schema: String,
+            #[serde(flatten)]
+            config: &'a Configuration,
+        }
+
+        // Point editors at the schema, in whichever way their language servers expect for each format
+        let serialized = match self.format {
+            ConfigFormat::Json => self.format.serialize(&WithSchema {
+                schema: format!("./{SCHEMA_PATH}"),
+                config: self,
+            })?,
+            ConfigFormat::Yaml => format!(
+                "# yaml-language-server: $schema=./{SCHEMA_PATH}\n{}",
+                self.format.serialize(&self)?
+            ),
+            ConfigFormat::Toml => format!("#:schema ./{SCHEMA_PATH}\n\n{}", self.format.serialize(&self)?),
+        };
+        Ok(serialized)
+    }
+
+    /// Every distinct system in use, ie. the default system & each host's.
+    pub fn systems(&self) -> BTreeSet<String> {
+        self.hosts
+            .values()
+            .map(|h| h.system.clone())
+            .chain([self.init.system.clone()])
+            .collect()
+    }
+
+    /// Names that are in scope where dev packages are rendered into the flake.
+    pub fn flake_scope(&self) -> Vec<String> {
+        let mut scope: Vec<String> = ["pkgs", "inputs"]
+            .into_iter()
+            .chain(RESERVED_INPUTS)
+            .map(String::from)
+            .collect();
+        scope.extend(self.resources.extra_flakes.iter().map(|f| f.ident.clone()));
+        scope
+    }
+
     pub fn render_flake(&self, context: Context) -> crate::Result<String> {
-        let extra_flakes = ""; // TODO: Flake management
-        let dev_packages = ""; // TODO: Extra dev pkgs
 
         let data = json!({
            "init": {
                 "description": self.init.description.clone(),
                 "nix": self.init.nix.clone(),
+                "nixpkgs_url": format!("nixpkgs/nixos-{}", self.init.nix),
                 "sops_url": self.init.sops_url.clone(),
                 "comin_url": self.init.comin_url.clone(),
                 "system": self.init.system.clone()
            },
            "resources": {
-                "extra_flakes": extra_flakes,
-                "dev_packages": dev_packages
-           }
+                "extra_flakes": self.resources.extra_flakes.clone(),
+                "dev_packages": self.resources.dev_packages.clone()
+           },
+           "hosts": self.hosts.values().collect::<Vec<_>>(),
+           "systems": self.systems()
         });
 
         context.render_template("flake/root.nix", &data)
     }
+
+    /// Renders the comin module shared by all hosts, which polls every configured remote.
+    pub fn render_comin(&self, context: Context) -> crate::Result<String> {
+        let mut remotes: Vec<&GitRemote> = self.resources.remotes.values().collect();
+        remotes.sort_by(|a, b| a.name.cmp(&b.name));
+
+        let data = json!({
+            "remotes": remotes
+                .into_iter()
+                .map(|r| r.as_nix().replace('\n', "\n      "))
+                .collect::<Vec<_>>()
+        });
+
+        context.render_template("modules/comin.nix", &data)
+    }
+
+    /// Resolves host names & `@group`s into hosts (without duplicates), or returns the first target that matches nothing.
+    pub fn resolve_hosts(&self, targets: &[String]) -> Result<Vec<&Host>, String> {
+        let mut hosts: Vec<&Host> = vec![];
+        for target in targets {
+            let matched = self.scope_hosts(&SecretScope::parse(target));
+            if matched.is_empty() {
+                return Err(target.clone());
+            }
+            for host in matched {
+                if !hosts.iter().any(|h| h.name == host.name) {
+                    hosts.push(host);
+                }
+            }
+        }
+        Ok(hosts)
+    }
+
+    /// Hosts that can read secrets in the given scope.
+    pub fn scope_hosts(&self, scope: &SecretScope) -> Vec<&Host> {
+        self.hosts
+            .values()
+            .filter(|host| match scope {
+                SecretScope::Host(name) => &host.name == name,
+                SecretScope::Group(group) => host.tags.contains(group),
+            })
+            .collect()
+    }
+
+    /// Secrets a host should have declared, keyed by name. Host secrets take precedence over group secrets of the same name.
+    pub fn host_secrets(&self, host: &Host) -> BTreeMap<String, &Secret> {
+        let (own, groups): (Vec<&Secret>, Vec<&Secret>) = self
+            .secrets
+            .files
+            .iter()
+            .filter(|secret| match &secret.scope {
+                SecretScope::Host(name) => name == &host.name,
+                SecretScope::Group(group) => host.tags.contains(group),
+            })
+            .partition(|secret| matches!(secret.scope, SecretScope::Host(_)));
+
+        groups
+            .into_iter()
+            .chain(own)
+            .map(|secret| (secret.name.clone(), secret))
+            .collect()
+    }
+
+    /// Renders a host's `secrets.nix`, declaring every secret it can decrypt for sops-nix.
+    pub fn render_host_secrets(&self, context: Context, host: &Host) -> crate::Result<String> {
+        let secrets: Vec<serde_json::Value> = self
+            .host_secrets(host)
+            .into_values()
+            .map(|secret| {
+                json!({
+                    "name": secret.name,
+                    "path": secret.path(),
+                    "owner": secret.owner,
+                    "group": secret.group,
+                    "mode": secret.mode,
+                    "restart_units": secret.restart_units,
+                })
+            })
+            .collect();
+
+        let data = json!({
+            "host": host.name,
+            "default_file": secrets.first().map(|s| s["path"].clone()),
+            "secrets": secrets,
+        });
+        context.render_template("host/secrets.nix", &data)
+    }
+
+    /// Groups that are either declared or applied to a host, ie. the ones that have a module.
+    pub fn host_groups(&self) -> BTreeSet<String> {
+        self.groups
+            .iter()
+            .cloned()
+            .chain(self.hosts.values().flat_map(|h| h.tags.iter().cloned()))
+            .collect()
+    }
+
+    /// All groups that are either declared, applied to a host or have secrets.
+    pub fn all_groups(&self) -> BTreeSet<String> {
+        self.host_groups()
+            .into_iter()
+            .chain(self.secrets.files.iter().filter_map(|s| match &s.scope {
+                SecretScope::Group(group) => Some(group.clone()),
+                SecretScope::Host(_) => None,
+            }))
+            .collect()
+    }
+
+    /// Age recipients that secrets in the given scope should be encrypted for (admins & matching hosts).
+    pub fn recipients(&self, scope: &SecretScope) -> BTreeSet<String> {
+        self.secrets
+            .admins
+            .values()
+            .cloned()
+            .chain(self.scope_hosts(scope).into_iter().filter_map(|h| h.age_key.clone()))
+            .collect()
+    }
+
+    /// Renders `.sops.yaml`, with one creation rule per host & group plus an admin-only fallback.
+    pub fn render_sops(&self, context: Context) -> crate::Result<String> {
+        let scopes = self
+            .hosts
+            .keys()
+            .map(|h| SecretScope::Host(h.clone()))
+            .chain(self.all_groups().into_iter().map(SecretScope::Group));
+
+        let rules: Vec<serde_json::Value> = scopes
+            .map(|scope| (format!("^{}/.*$", scope.directory().to_str().unwrap()), self.recipients(&scope)))
+            .chain([(String::from("^secrets/.*$"), self.secrets.admins.values().cloned().collect())])
+            .filter(|(_, recipients)| !recipients.is_empty())
+            .map(|(path_regex, recipients)| json!({"path_regex": path_regex, "recipients": recipients}))
+            .collect();
+
+        context.render_template("secrets/sops.yaml", &json!({ "rules": rules }))
+    }
+
+    /// Renders every file nico manages, as paths relative to the project root.
+    pub fn render_files(&self, context: Context) -> crate::Result<Vec<(PathBuf, String)>> {
+        let mut files = vec![
+            (PathBuf::from("flake.nix"), self.render_flake(context.clone())?),
+            (PathBuf::from("modules/comin.nix"), self.render_comin(context.clone())?),
+            (PathBuf::from(".sops.yaml"), self.render_sops(context.clone())?),
+            (PathBuf::from(".envrc"), String::from("use flake")),
+            (PathBuf::from(SCHEMA_PATH), Self::schema()?),
+        ];
+
+        for host in self.hosts.values() {
+            files.push((
+                host.directory().join("secrets.nix"),
+                self.render_host_secrets(context.clone(), host)?,
+            ));
+        }
+        Ok(files.into_iter().map(|(path, content)| (path, regions::stamp(&content))).collect())
+    }
+
+    /// Renders every managed file & merges it with what's currently in the project root, so only managed regions
+    /// of existing files are replaced. Fails if a managed region was edited by hand, unless `--force` was given.
+    pub fn merged_files(&self, context: Context, root: impl AsRef<Path>) -> crate::Result<Vec<(PathBuf, String)>> {
+        let root = root.as_ref();
+        let mut files = vec![];
+        for (path, rendered) in self.render_files(context.clone())? {
+            let existing = fs::read_to_string(root.join(&path)).map(|existing| match path.to_str() {
+                Some("flake.nix") => LEGACY_FLAKE.iter().fold(existing, |text, legacy| regions::adopt(&text, legacy)),
+                _ => existing,
+            });
+            let content = match existing {
+                // A file without any regions can't be merged, so it's only replaced with `--force`
+                Ok(existing) if regions::has_regions(&rendered) => {
+                    regions::merge(&existing, &rendered, context.force)
+                        .map_err(|regions| crate::Error::ModifiedRegions(path.clone(), regions))?
+                }
+                _ => rendered,
+            };
+            files.push((path, content));
+        }
+        Ok(files)
+    }
+
+    /// Every file [`Configuration::write_files`] writes, with its content: the managed files merged into their
+    /// existing content & any group modules that haven't been scaffolded yet. The config itself is only included
+    /// while it's missing or needs migrating, since rewriting it would drop any comments in it.
+    pub fn project_files(&self, context: Context, root: impl AsRef<Path>) -> crate::Result<Vec<(PathBuf, String)>> {
+        let root = root.as_ref();
+        let mut files = vec![];
+        if !Self::file_version(root).is_ok_and(|version| version >= CONFIG_VERSION) {
+            files.push((PathBuf::from(self.format.file_name()), self.serialize()?));
+        }
+        files.extend(self.merged_files(context.clone(), root)?);
+
+        // Group modules are only scaffolded, since they're for the user to fill in
+        for group in self.host_groups() {
+            let path = group_module(&group);
+            if !root.join(&path).exists() {
+                files.push((path, context.render_template("group/default.nix", &json!({"name": group}))?));
+            }
+        }
+        Ok(files)
+    }
+
+    /// (Re)writes all managed files into the project root, returning their paths. Use [`Configuration::save`] to
+    /// save changes to the config itself.
+    pub fn write_files(&self, context: Context, root: impl AsRef<Path>) -> crate::Result<Vec<PathBuf>> {
+        let root = root.as_ref();
+        let mut written = vec![];
+        for (path, content) in self.project_files(context, root)? {
+            debug!("Writing {path:?}");
+            if let Some(parent) = root.join(&path).parent() {
+                fs::create_dir_all(parent)?;
+            }
+            fs::write(root.join(&path), content)?;
+            written.push(path);
+        }
+        Ok(written)
+    }
 }
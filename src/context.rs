@@ -1,6 +1,13 @@
-use std::{fmt::Display, ops::Deref, path::PathBuf, sync::Arc};
+use std::{
+    fmt::Display,
+    fs,
+    ops::Deref,
+    path::{Path, PathBuf},
+    sync::Arc,
+};
 
 use clap::{Command, CommandFactory, Parser, error::ErrorKind};
+use git2::Repository;
 use handlebars::Handlebars;
 use include_directory::{Dir, include_directory};
 use log::{debug, trace};
@@ -10,10 +17,68 @@ use serde::Serialize;
 use crate::{
     cli::{Cli, Operations},
     config::Configuration,
+    nix,
 };
 
 static TEMPLATES: Dir<'_> = include_directory!("$CARGO_MANIFEST_DIR/templates");
 
+/// Directory (relative to the project root) that templates overriding or extending the built-in ones are loaded from
+pub const TEMPLATE_OVERRIDES: &str = ".nico/templates";
+
+/// Built-in templates as (key, raw content), where the key is the template's path minus `.template`.
+pub fn builtin_templates() -> Vec<(String, &'static str)> {
+    let mut templates: Vec<(String, &'static str)> = TEMPLATES
+        .find("**/*.template")
+        .unwrap()
+        .filter_map(|v| v.as_file())
+        .map(|file| {
+            let path = file.path().to_str().unwrap();
+            (path.rsplit_once(".").unwrap().0.to_string(), file.contents_utf8().unwrap())
+        })
+        .collect();
+    templates.sort_by(|a, b| a.0.cmp(&b.0));
+    templates
+}
+
+/// Template overrides in a project as (key, path), using the same key scheme as the built-in templates.
+pub fn template_overrides(root: impl AsRef<Path>) -> crate::Result<Vec<(String, PathBuf)>> {
+    fn walk(directory: &Path, base: &Path, found: &mut Vec<(String, PathBuf)>) -> crate::Result<()> {
+        for entry in fs::read_dir(directory)? {
+            let path = entry?.path();
+            if path.is_dir() {
+                walk(&path, base, found)?;
+            } else if let Some(key) = path
+                .strip_prefix(base)
+                .ok()
+                .and_then(|p| p.to_str())
+                .and_then(|p| p.strip_suffix(".template"))
+            {
+                found.push((key.to_string(), path.clone()));
+            }
+        }
+        Ok(())
+    }
+
+    let base = root.as_ref().join(TEMPLATE_OVERRIDES);
+    let mut found = vec![];
+    if base.is_dir() {
+        walk(&base, &base, &mut found)?;
+    }
+    found.sort();
+    Ok(found)
+}
+
+/// Registers a template, stripping the `#! ` prefixes that hide template logic in nix comments.
+fn register_template(templater: &mut Handlebars<'static>, key: &str, content: &str) -> crate::Result<()> {
+    trace!("Adding template with key {key}.");
+    let cleaned = if key.ends_with(".nix") {
+        content.replace("#! ", "")
+    } else {
+        content.to_string()
+    };
+    Ok(templater.register_template_string(key, cleaned)?)
+}
+
 #[derive(Clone, Debug)]
 pub struct Context {
     input: Cli,
@@ -26,47 +91,34 @@ pub struct Context {
 impl Context {
     pub fn new() -> crate::Result<Option<Self>> {
         let parsed = Cli::parse();
-        if let Some(project) = parsed.project.clone() {
-            if !parsed.ignore_project {
-                let project = project.to_str().unwrap().to_string();
-                let mut args = vec![
-                    "develop".to_string(),
-                    project.clone(),
-                    "--command".to_string(),
-                    std::env::current_exe()?.to_str().unwrap().to_string(),
-                    "--ignore-project".to_string(),
-                ];
-                args.extend(std::env::args().collect::<Vec<String>>()[1..].to_vec());
-                std::process::Command::new("nix")
-                    .args(args)
-                    .env("NICO_OVERRIDE_ENV", project.clone())
-                    .current_dir(project.clone())
-                    .status()?;
-                return Ok(None);
-            }
+        if let Some(project) = parsed.project.clone()
+            && !parsed.ignore_project
+        {
+            let project = project.to_str().unwrap().to_string();
+            let mut args = vec![
+                "develop".to_string(),
+                project.clone(),
+                "--command".to_string(),
+                std::env::current_exe()?.to_str().unwrap().to_string(),
+                "--ignore-project".to_string(),
+            ];
+            args.extend(std::env::args().collect::<Vec<String>>()[1..].to_vec());
+            std::process::Command::new("nix")
+                .args(args)
+                .env("NICO_OVERRIDE_ENV", project.clone())
+                .current_dir(project.clone())
+                .status()?;
+            return Ok(None);
         }
 
         let mut templater = Handlebars::new();
+        // Templates render to nix/shell sources, so HTML escaping would only mangle them
+        templater.register_escape_fn(handlebars::no_escape);
+        nix::register_helpers(&mut templater);
 
         debug!("Loading templates...");
-        for file in TEMPLATES
-            .find("**/*.template")
-            .unwrap()
-            .filter_map(|v| v.as_file())
-        {
-            let path = file.path().to_str().unwrap().to_string();
-            let key = path.rsplit_once(".").unwrap().0.to_string();
-
-            trace!("Adding template with key {key}.");
-            let content = file.contents_utf8().unwrap();
-            let cleaned = if key.ends_with(".nix") {
-                content.replace("#! ", "")
-            } else {
-                content.to_string()
-            };
-            templater
-                .register_template_string(&key, &cleaned)
-                .expect("Failed to load internal template.");
+        for (key, content) in builtin_templates() {
+            register_template(&mut templater, &key, content).expect("Failed to load internal template.");
         }
 
         let (config, project_root) = match parsed.operation.clone() {
@@ -84,6 +136,13 @@ impl Context {
             }
         };
 
+        if let Some(root) = project_root.as_ref() {
+            for (key, path) in template_overrides(root)? {
+                debug!("Loading template override {key} from {path:?}");
+                register_template(&mut templater, &key, &fs::read_to_string(&path)?)?;
+            }
+        }
+
         Ok(Some(Self {
             input: parsed,
             command: Arc::new(Mutex::new(Cli::command())),
@@ -113,6 +172,13 @@ impl Context {
     pub fn project_root(&self) -> Option<PathBuf> {
         self.project_root.clone()
     }
+
+    pub fn repository(&self) -> crate::Result<Repository> {
+        match self.project_root() {
+            Some(root) => Ok(Repository::open(root)?),
+            None => Err(crate::Error::OutsideShell),
+        }
+    }
 }
 
 impl Deref for Context {
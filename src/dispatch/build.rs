@@ -0,0 +1,156 @@
+use std::{
+    path::{Path, PathBuf},
+    process::Command,
+    time::Instant,
+};
+
+use clap::error::ErrorKind;
+use colored::Colorize;
+use log::{debug, info};
+use serde::Serialize;
+
+use crate::{
+    cli::BuildArgs,
+    context::Context,
+    dispatch::{Dispatcher, failure_summary},
+    ensure_dependency,
+};
+
+/// The result of building one host's toplevel.
+#[derive(Serialize, Clone, Debug)]
+struct BuildOutcome {
+    host: String,
+    success: bool,
+    store_path: Option<PathBuf>,
+    seconds: f64,
+    error: Option<String>,
+}
+
+/// Builds a host's `config.system.build.toplevel` from the flake in `flake_root`, returning its store path or why
+/// the build failed.
+pub(crate) fn build_toplevel(flake_root: &Path, host: &str) -> Result<PathBuf, String> {
+    let installable = format!(".#nixosConfigurations.{host}.config.system.build.toplevel");
+    debug!("Building {installable} in {flake_root:?}");
+    let output = Command::new("nix")
+        .args(["build", &installable, "--no-link", "--print-out-paths"])
+        .current_dir(flake_root)
+        .output()
+        .map_err(|e| e.to_string())?;
+    if !output.status.success() {
+        return Err(failure_summary("nix build", &output));
+    }
+
+    String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .map(str::trim)
+        .find(|l| !l.is_empty())
+        .map(PathBuf::from)
+        .ok_or_else(|| String::from("nix build didn't print a store path"))
+}
+
+fn print_summary(outcomes: &[BuildOutcome]) {
+    let host_width = outcomes.iter().map(|o| o.host.len()).chain([4]).max().unwrap();
+
+    println!();
+    println!("{}", format!("{:host_width$}  {:6}  {:>7}  STORE PATH", "HOST", "RESULT", "TIME").bold());
+    for outcome in outcomes.iter() {
+        let result = if outcome.success {
+            format!("{:6}", "ok").green()
+        } else {
+            format!("{:6}", "failed").red()
+        };
+        let store_path = outcome
+            .store_path
+            .as_ref()
+            .map(|p| p.to_str().unwrap().to_string())
+            .unwrap_or_default();
+        println!(
+            "{}  {result}  {:>6.1}s  {}",
+            format!("{:host_width$}", outcome.host).bright_white().bold(),
+            outcome.seconds,
+            store_path.dimmed()
+        );
+    }
+
+    for outcome in outcomes.iter() {
+        if let Some(error) = &outcome.error {
+            println!("\n{}", format!("{} failed:", outcome.host).red().bold());
+            println!("{}", error.dimmed());
+        }
+    }
+}
+
+pub struct BuildDispatcher;
+impl Dispatcher for BuildDispatcher {
+    type Args = BuildArgs;
+    fn dispatch(context: Context, args: Self::Args) -> crate::Result<()> {
+        let root = context.project_root().unwrap();
+        let config = context.config().unwrap();
+
+        let hosts: Vec<String> = if args.targets.is_empty() {
+            config.hosts.keys().cloned().collect()
+        } else {
+            config
+                .resolve_hosts(&args.targets)
+                .map_err(|target| {
+                    context.error(
+                        ErrorKind::ValueValidation,
+                        format!("{target} doesn't match any host."),
+                    )
+                })?
+                .into_iter()
+                .map(|h| h.name.clone())
+                .collect()
+        };
+        if hosts.is_empty() {
+            println!("{}", "No hosts configured.".italic());
+            return Ok(());
+        }
+
+        let version = ensure_dependency("nix", ["--version"])?;
+        info!("Building {} host(s) with {version}", hosts.len());
+
+        let mut outcomes = vec![];
+        for host in hosts.iter() {
+            if !args.json {
+                println!("{} {}", "Building".cyan(), host.bright_white().bold());
+            }
+            let start = Instant::now();
+            let result = build_toplevel(&root, host);
+            let seconds = start.elapsed().as_secs_f64();
+            outcomes.push(match result {
+                Ok(store_path) => BuildOutcome {
+                    host: host.clone(),
+                    success: true,
+                    store_path: Some(store_path),
+                    seconds,
+                    error: None,
+                },
+                Err(error) => BuildOutcome {
+                    host: host.clone(),
+                    success: false,
+                    store_path: None,
+                    seconds,
+                    error: Some(error),
+                },
+            });
+        }
+
+        if args.json {
+            println!("{}", serde_json::to_string_pretty(&outcomes)?);
+        } else {
+            print_summary(&outcomes);
+        }
+
+        let failed: Vec<String> = outcomes
+            .into_iter()
+            .filter(|o| !o.success)
+            .map(|o| o.host)
+            .collect();
+        if failed.is_empty() {
+            Ok(())
+        } else {
+            Err(crate::Error::HostsFailed(String::from("Build"), failed))
+        }
+    }
+}
@@ -0,0 +1,222 @@
+use std::{fs, path::PathBuf};
+
+use clap::error::ErrorKind;
+use colored::Colorize;
+use serde_json::Value;
+
+use crate::{
+    cli::{
+        ConfigArgs, ConfigConvertArgs, ConfigGetArgs, ConfigMigrateArgs, ConfigOperations,
+        ConfigSchemaArgs, ConfigSetArgs, ConfigUnsetArgs, ConfigWriteArgs,
+    },
+    config::{CONFIG_VERSION, Configuration},
+    context::Context,
+    dispatch::{Dispatcher, save_project},
+    repo::RepoExt,
+};
+
+/// Follows a dotted path through objects (by key) & arrays (by index).
+fn lookup<'a>(value: &'a Value, path: &str) -> Option<&'a Value> {
+    path.split('.').try_fold(value, |current, segment| match current {
+        Value::Object(map) => map.get(segment),
+        Value::Array(items) => items.get(segment.parse::<usize>().ok()?),
+        _ => None,
+    })
+}
+
+/// Like [`lookup`], but creates missing objects along the way. Fails with the path that couldn't be traversed.
+fn lookup_or_create<'a>(value: &'a mut Value, path: &str) -> Result<&'a mut Value, String> {
+    let mut current = value;
+    let mut traversed = vec![];
+    for segment in path.split('.') {
+        traversed.push(segment);
+        current = match current {
+            Value::Object(map) => map.entry(segment).or_insert(Value::Null),
+            Value::Array(items) => segment
+                .parse::<usize>()
+                .ok()
+                .and_then(|index| items.get_mut(index))
+                .ok_or(traversed.join("."))?,
+            Value::Null => {
+                *current = Value::Object(Default::default());
+                current.as_object_mut().unwrap().entry(segment).or_insert(Value::Null)
+            }
+            _ => return Err(traversed.join(".")),
+        };
+    }
+    Ok(current)
+}
+
+/// Validates an edited config, then saves it (regenerating files & committing if requested).
+fn write(context: Context, edited: Value, args: ConfigWriteArgs, message: String) -> crate::Result<()> {
+    let root = context.project_root().unwrap();
+    let format = context.config().unwrap().format;
+    let config = match serde_json::from_value::<Configuration>(edited) {
+        Ok(config) => Configuration { format, ..config },
+        Err(error) => {
+            return Err(context.error(
+                ErrorKind::ValueValidation,
+                format!("The change would make the config invalid: {error}"),
+            ));
+        }
+    };
+
+    let written = if args.regen {
+        save_project(context.clone(), &config)?
+    } else {
+        config.save(&root)?;
+        context.repository()?.add_files([format.file_name()])?;
+        vec![PathBuf::from(format.file_name())]
+    };
+
+    // Only what was just written is committed, anything else the user has staged stays staged
+    if args.commit {
+        context
+            .repository()?
+            .commit_paths(message, written.iter().map(|p| p.to_str().unwrap()))?;
+    }
+    Ok(())
+}
+
+fn get(context: Context, args: ConfigGetArgs) -> crate::Result<()> {
+    let config = serde_json::to_value(context.config().unwrap())?;
+    let value = match args.path.as_deref() {
+        Some(path) => lookup(&config, path),
+        None => Some(&config),
+    };
+
+    match value {
+        Some(Value::String(s)) => println!("{s}"),
+        Some(value) => println!("{}", serde_json::to_string_pretty(value)?),
+        None => {
+            return Err(context.error(
+                ErrorKind::ValueValidation,
+                format!("{} isn't set in the config.", args.path.unwrap()),
+            ));
+        }
+    }
+    Ok(())
+}
+
+fn set(context: Context, args: ConfigSetArgs) -> crate::Result<()> {
+    let mut config = serde_json::to_value(context.config().unwrap())?;
+    let value = match serde_json::from_str::<Value>(&args.value) {
+        Ok(parsed) if !args.string => parsed,
+        _ => Value::String(args.value.clone()),
+    };
+
+    match lookup_or_create(&mut config, &args.path) {
+        Ok(target) => *target = value,
+        Err(path) => {
+            return Err(context.error(
+                ErrorKind::ValueValidation,
+                format!("{path} can't contain {}.", args.path),
+            ));
+        }
+    }
+
+    write(context, config, args.write, format!("Set {} in the nico config", args.path))?;
+    println!("Set {} to {}", args.path.bright_white().bold(), args.value.italic());
+    Ok(())
+}
+
+fn unset(context: Context, args: ConfigUnsetArgs) -> crate::Result<()> {
+    let mut config = serde_json::to_value(context.config().unwrap())?;
+    let (parent, key) = match args.path.rsplit_once('.') {
+        Some((parent, key)) => (lookup_or_create(&mut config, parent).ok(), key),
+        None => (Some(&mut config), args.path.as_str()),
+    };
+
+    let removed = match parent {
+        Some(Value::Object(map)) => map.remove(key).is_some(),
+        Some(Value::Array(items)) => match key.parse::<usize>() {
+            Ok(index) if index < items.len() => {
+                items.remove(index);
+                true
+            }
+            _ => false,
+        },
+        _ => false,
+    };
+    if !removed {
+        return Err(context.error(
+            ErrorKind::ValueValidation,
+            format!("{} isn't set in the config.", args.path),
+        ));
+    }
+
+    write(context, config, args.write, format!("Unset {} in the nico config", args.path))?;
+    println!("Unset {}", args.path.bright_white().bold());
+    Ok(())
+}
+
+fn migrate(context: Context, _: ConfigMigrateArgs) -> crate::Result<()> {
+    let root = context.project_root().unwrap();
+    let version = Configuration::file_version(&root)?;
+    if version == CONFIG_VERSION {
+        println!("{}", format!("The config is already at version {CONFIG_VERSION}.").italic());
+        return Ok(());
+    }
+
+    // The context's config was already migrated when it was loaded
+    let config = context.config().unwrap();
+    config.save(&root)?;
+    let repo = context.repository()?;
+    repo.add_files([config.format.file_name()])?;
+    // Only the config is committed, anything else the user has staged stays staged
+    repo.commit_paths(
+        format!("Migrate nico config from version {version} to {CONFIG_VERSION}"),
+        [config.format.file_name()],
+    )?;
+
+    println!(
+        "Migrated the config from version {} to {}",
+        version.to_string().bright_white().bold(),
+        CONFIG_VERSION.to_string().bright_white().bold()
+    );
+    Ok(())
+}
+
+fn convert(context: Context, args: ConfigConvertArgs) -> crate::Result<()> {
+    let root = context.project_root().unwrap();
+    let mut config = context.config().unwrap();
+    let from = config.format;
+    if from == args.to {
+        println!("{}", format!("The config is already stored in {}.", from.file_name()).italic());
+        return Ok(());
+    }
+
+    config.format = args.to;
+    config.save(&root)?;
+    fs::remove_file(root.join(from.file_name()))?;
+    context
+        .repository()?
+        .add_files([from.file_name(), args.to.file_name()])?;
+
+    println!(
+        "Converted {} to {}",
+        from.file_name().bright_white().bold(),
+        args.to.file_name().bright_white().bold()
+    );
+    Ok(())
+}
+
+fn schema(_: Context, _: ConfigSchemaArgs) -> crate::Result<()> {
+    println!("{}", Configuration::schema()?);
+    Ok(())
+}
+
+pub struct ConfigDispatcher;
+impl Dispatcher for ConfigDispatcher {
+    type Args = ConfigArgs;
+    fn dispatch(context: Context, args: Self::Args) -> crate::Result<()> {
+        match args.operation {
+            ConfigOperations::Get(args) => get(context, args),
+            ConfigOperations::Set(args) => set(context, args),
+            ConfigOperations::Unset(args) => unset(context, args),
+            ConfigOperations::Migrate(args) => migrate(context, args),
+            ConfigOperations::Convert(args) => convert(context, args),
+            ConfigOperations::Schema(args) => schema(context, args),
+        }
+    }
+}
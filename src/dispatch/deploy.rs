@@ -0,0 +1,230 @@
+use std::{
+    collections::VecDeque,
+    path::Path,
+    process::{Command, Stdio},
+    time::{Duration, Instant},
+};
+
+use clap::{ValueEnum, error::ErrorKind};
+use colored::Colorize;
+use log::debug;
+use parking_lot::Mutex;
+
+use crate::{
+    cli::DeployArgs,
+    config::{DeployTarget, Host},
+    context::Context,
+    dispatch::{Dispatcher, failure_summary},
+    find_dependency,
+};
+
+struct Outcome {
+    host: String,
+    destination: String,
+    elapsed: Duration,
+    /// Why the deploy failed, if it did
+    failure: Option<String>,
+}
+
+/// Runs nixos-rebuild for a host. Interactive deploys run in the foreground, so nixos-rebuild can prompt for
+/// passwords; otherwise its output is captured & summarized if it fails.
+fn deploy_host(root: &Path, action: &str, host: &str, target: &DeployTarget, interactive: bool) -> Outcome {
+    let destination = target.destination();
+    let mut args = vec![
+        action.to_string(),
+        "--flake".to_string(),
+        format!(".#{host}"),
+        "--target-host".to_string(),
+        destination.clone(),
+    ];
+    if target.sudo {
+        args.push("--use-remote-sudo".to_string());
+    }
+    if target.build_on_target {
+        args.extend(["--build-host".to_string(), destination.clone()]);
+    }
+
+    debug!("Running nixos-rebuild {}", args.join(" "));
+    let start = Instant::now();
+    let mut command = Command::new("nixos-rebuild");
+    command.args(args).current_dir(root);
+    let failure = if interactive {
+        match command.stdin(Stdio::inherit()).stdout(Stdio::inherit()).stderr(Stdio::inherit()).status() {
+            Ok(status) if status.success() => None,
+            Ok(status) => Some(format!("nixos-rebuild exited with {status}")),
+            Err(e) => Some(e.to_string()),
+        }
+    } else {
+        match command.output() {
+            Ok(output) if output.status.success() => None,
+            Ok(output) => Some(failure_summary("nixos-rebuild", &output)),
+            Err(e) => Some(e.to_string()),
+        }
+    };
+
+    Outcome {
+        host: host.to_string(),
+        destination,
+        elapsed: start.elapsed(),
+        failure,
+    }
+}
+
+fn print_summary(outcomes: &[Outcome]) {
+    let host_width = outcomes.iter().map(|o| o.host.len()).chain([4]).max().unwrap();
+    let target_width = outcomes.iter().map(|o| o.destination.len()).chain([6]).max().unwrap();
+
+    println!();
+    println!(
+        "{}",
+        format!("{:host_width$}  {:target_width$}  {:6}  TIME", "HOST", "TARGET", "RESULT").bold()
+    );
+    for outcome in outcomes.iter() {
+        let result = match outcome.failure {
+            None => format!("{:6}", "ok").green(),
+            Some(_) => format!("{:6}", "failed").red(),
+        };
+        println!(
+            "{}  {:target_width$}  {result}  {:.1}s",
+            format!("{:host_width$}", outcome.host).bright_white().bold(),
+            outcome.destination,
+            outcome.elapsed.as_secs_f64()
+        );
+    }
+
+    for outcome in outcomes.iter() {
+        if let Some(failure) = &outcome.failure {
+            println!("\n{}", format!("{} failed:", outcome.host).red().bold());
+            println!("{}", failure.dimmed());
+        }
+    }
+}
+
+pub struct DeployDispatcher;
+impl Dispatcher for DeployDispatcher {
+    type Args = DeployArgs;
+    fn dispatch(context: Context, args: Self::Args) -> crate::Result<()> {
+        let root = context.project_root().unwrap();
+        let config = context.config().unwrap();
+
+        let hosts: Vec<&Host> = config.resolve_hosts(&args.targets).map_err(|target| {
+            context.error(
+                ErrorKind::ValueValidation,
+                format!("{target} doesn't match any host."),
+            )
+        })?;
+        let missing: Vec<&str> = hosts
+            .iter()
+            .filter(|h| h.deploy.is_none())
+            .map(|h| h.name.as_str())
+            .collect();
+        if !missing.is_empty() {
+            return Err(context.error(
+                ErrorKind::ValueValidation,
+                format!(
+                    "No deploy target is set for {}, set one with `nico host target <host> <address>`.",
+                    missing.join(", ")
+                ),
+            ));
+        }
+        if args.jobs == 0 {
+            return Err(context.error(ErrorKind::ValueValidation, "--jobs must be at least 1."));
+        }
+        find_dependency("nixos-rebuild")?;
+
+        let action = args.action.to_possible_value().unwrap().get_name().to_string();
+        let queue: Mutex<VecDeque<&Host>> = Mutex::new(hosts.iter().copied().collect());
+        let outcomes: Mutex<Vec<Outcome>> = Mutex::new(vec![]);
+        std::thread::scope(|scope| {
+            for _ in 0..args.jobs.min(hosts.len()) {
+                scope.spawn(|| {
+                    loop {
+                        let next = queue.lock().pop_front();
+                        let Some(host) = next else { break };
+                        let target = host.deploy.as_ref().unwrap();
+                        println!("{} {} ({})", "Deploying".cyan(), host.name.bright_white().bold(), target.destination());
+
+                        let outcome = deploy_host(&root, &action, &host.name, target, args.jobs == 1);
+                        match outcome.failure {
+                            None => println!("{} {}", "Deployed".green(), host.name.bright_white().bold()),
+                            Some(_) => println!("{} {}", "Failed".red(), host.name.bright_white().bold()),
+                        }
+                        outcomes.lock().push(outcome);
+                    }
+                });
+            }
+        });
+
+        // Report hosts in the order they were requested, not the order they finished in
+        let mut outcomes = outcomes.into_inner();
+        outcomes.sort_by_key(|o| hosts.iter().position(|h| h.name == o.host));
+        print_summary(&outcomes);
+
+        let failed: Vec<String> = outcomes
+            .into_iter()
+            .filter(|o| o.failure.is_some())
+            .map(|o| o.host)
+            .collect();
+        if failed.is_empty() {
+            Ok(())
+        } else {
+            Err(crate::Error::HostsFailed(String::from("Deploy"), failed))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{fs, os::unix::fs::PermissionsExt};
+
+    use super::*;
+
+    #[test]
+    fn runs_nixos_rebuild_from_path() {
+        let directory = std::env::temp_dir().join(format!("nico-deploy-test-{}", std::process::id()));
+        fs::create_dir_all(&directory).unwrap();
+        let stub = directory.join("nixos-rebuild");
+        fs::write(
+            &stub,
+            "#!/bin/sh\nprintf '%s\\n' \"$@\" > \"$0.args\"\n[ \"$3\" = .#broken ] && echo 'error: unreachable' >&2 && exit 1\nexit 0\n",
+        )
+        .unwrap();
+        fs::set_permissions(&stub, fs::Permissions::from_mode(0o755)).unwrap();
+
+        let path = std::env::var_os("PATH").unwrap_or_default();
+        let path = std::env::join_paths([directory.clone()].into_iter().chain(std::env::split_paths(&path))).unwrap();
+        // SAFETY: no other test reads or writes the environment
+        unsafe { std::env::set_var("PATH", path) };
+        assert_eq!(find_dependency("nixos-rebuild").unwrap(), stub);
+
+        let args = || fs::read_to_string(directory.join("nixos-rebuild.args")).unwrap();
+        let plain = DeployTarget {
+            address: String::from("10.0.0.1"),
+            user: None,
+            sudo: false,
+            build_on_target: false,
+        };
+        let outcome = deploy_host(&directory, "switch", "web", &plain, false);
+        assert_eq!(outcome.failure, None);
+        assert_eq!(outcome.destination, "10.0.0.1");
+        assert_eq!(args(), "switch\n--flake\n.#web\n--target-host\n10.0.0.1\n");
+
+        let remote = DeployTarget {
+            address: String::from("pi.lan"),
+            user: Some(String::from("admin")),
+            sudo: true,
+            build_on_target: true,
+        };
+        let outcome = deploy_host(&directory, "boot", "pi", &remote, false);
+        assert_eq!(outcome.failure, None);
+        assert_eq!(
+            args(),
+            "boot\n--flake\n.#pi\n--target-host\nadmin@pi.lan\n--use-remote-sudo\n--build-host\nadmin@pi.lan\n"
+        );
+
+        let outcome = deploy_host(&directory, "switch", "broken", &plain, false);
+        assert_eq!(outcome.failure.as_deref(), Some("error: unreachable"));
+
+        fs::remove_dir_all(&directory).unwrap();
+    }
+}
@@ -0,0 +1,88 @@
+use clap::error::ErrorKind;
+use colored::Colorize;
+use log::*;
+
+use crate::{
+    cli::{DevshellAddArgs, DevshellArgs, DevshellListArgs, DevshellOperations, DevshellRemoveArgs},
+    config::parse_attr_path,
+    context::Context,
+    dispatch::{Dispatcher, save_project},
+};
+
+fn add(context: Context, args: DevshellAddArgs) -> crate::Result<()> {
+    let mut config = context.config().unwrap();
+    let scope = config.flake_scope();
+
+    for package in args.packages {
+        let Some(segments) = parse_attr_path(&package).filter(|s| s.len() > 1) else {
+            return Err(context.error(
+                ErrorKind::ValueValidation,
+                format!("{package} is not a valid attribute path."),
+            ));
+        };
+
+        if !scope.contains(&segments[0]) {
+            return Err(context.error(
+                ErrorKind::ValueValidation,
+                format!(
+                    "{package} doesn't refer to anything in the flake's scope (expected one of: {}).",
+                    scope.join(", ")
+                ),
+            ));
+        }
+
+        if config.resources.dev_packages.contains(&package) {
+            info!("{package} is already in the devshell, skipping.");
+            continue;
+        }
+
+        println!("Added {}", package.bright_white().bold());
+        config.resources.dev_packages.push(package);
+    }
+
+    save_project(context, &config)?;
+    Ok(())
+}
+
+fn remove(context: Context, args: DevshellRemoveArgs) -> crate::Result<()> {
+    let mut config = context.config().unwrap();
+
+    for package in args.packages {
+        if !config.resources.dev_packages.contains(&package) {
+            return Err(context.error(
+                ErrorKind::ValueValidation,
+                format!("{package} isn't in the devshell."),
+            ));
+        }
+
+        config.resources.dev_packages.retain(|p| p != &package);
+        println!("Removed {}", package.bright_white().bold());
+    }
+
+    save_project(context, &config)?;
+    Ok(())
+}
+
+fn list(context: Context, _: DevshellListArgs) -> crate::Result<()> {
+    let config = context.config().unwrap();
+    if config.resources.dev_packages.is_empty() {
+        println!("{}", "No extra devshell packages configured.".italic());
+    }
+
+    for package in config.resources.dev_packages {
+        println!("  - {}", package.bright_white());
+    }
+    Ok(())
+}
+
+pub struct DevshellDispatcher;
+impl Dispatcher for DevshellDispatcher {
+    type Args = DevshellArgs;
+    fn dispatch(context: Context, args: Self::Args) -> crate::Result<()> {
+        match args.operation {
+            DevshellOperations::Add(args) => add(context, args),
+            DevshellOperations::Remove(args) => remove(context, args),
+            DevshellOperations::List(args) => list(context, args),
+        }
+    }
+}
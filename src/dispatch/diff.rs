@@ -0,0 +1,166 @@
+use std::{path::Path, process::Command};
+
+use clap::error::ErrorKind;
+use colored::Colorize;
+use log::debug;
+
+use crate::{
+    cli::DiffArgs,
+    context::Context,
+    dispatch::{Dispatcher, build::build_toplevel, failure_summary},
+    repo::RepoExt,
+};
+
+/// Marks a version that's absent from one of the closures in `nix store diff-closures` output.
+const ABSENT: &str = "∅";
+
+/// One line of `nix store diff-closures` output, like `firefox: 120.0 → 121.0, +5.2 MiB`. A package with several
+/// versions in a closure lists all of them, eg. `perl: 5.36.0, 5.36.0-env → 5.38.0, +12.0 KiB`.
+#[derive(Debug, PartialEq)]
+struct ClosureChange<'a> {
+    name: &'a str,
+    versions: Option<(&'a str, &'a str)>,
+    size: Option<&'a str>,
+}
+
+impl<'a> ClosureChange<'a> {
+    fn parse(line: &'a str) -> Option<Self> {
+        let (name, rest) = line.split_once(": ")?;
+        // Versions are comma separated too, so only the last item can be the size change
+        let (versions, size) = match rest.rsplit_once(", ") {
+            Some((versions, size)) if size.starts_with(['+', '-']) => (Some(versions), Some(size.trim())),
+            None if rest.starts_with(['+', '-']) => (None, Some(rest.trim())),
+            _ => (Some(rest), None),
+        };
+        Some(Self {
+            name: name.trim(),
+            versions: versions
+                .and_then(|v| v.split_once(" → "))
+                .map(|(old, new)| (old.trim(), new.trim())),
+            size,
+        })
+    }
+
+    fn print(&self) {
+        let size = self.size.map(|s| format!(" ({s})")).unwrap_or_default();
+        let name = self.name.bright_white().bold();
+        match self.versions {
+            Some((ABSENT, new)) => println!("  {} {name} {}{}", "+".green().bold(), new.green(), size.dimmed()),
+            Some((old, ABSENT)) => println!("  {} {name} {}{}", "-".red().bold(), old.red(), size.dimmed()),
+            Some((old, new)) => println!(
+                "  {} {name} {} → {}{}",
+                "~".yellow().bold(),
+                old.dimmed(),
+                new.yellow(),
+                size.dimmed()
+            ),
+            None => println!("  {} {name}{}", "~".dimmed(), size.dimmed()),
+        }
+    }
+}
+
+fn build(flake_root: &Path, host: &str, label: &str) -> crate::Result<String> {
+    println!("{} {} at {}", "Building".cyan(), host.bright_white().bold(), label.italic());
+    build_toplevel(flake_root, host)
+        .map(|p| p.to_str().unwrap().to_string())
+        .map_err(|error| {
+            println!("\n{}", format!("{host} failed to build at {label}:").red().bold());
+            println!("{}", error.dimmed());
+            crate::Error::HostsFailed(String::from("Build"), vec![host.to_string()])
+        })
+}
+
+pub struct DiffDispatcher;
+impl Dispatcher for DiffDispatcher {
+    type Args = DiffArgs;
+    fn dispatch(context: Context, args: Self::Args) -> crate::Result<()> {
+        let root = context.project_root().unwrap();
+        let config = context.config().unwrap();
+        if !config.hosts.contains_key(&args.host) {
+            return Err(context.error(
+                ErrorKind::ValueValidation,
+                format!("No host named {} exists.", args.host),
+            ));
+        }
+
+        let old = {
+            let worktree = context.repository()?.temporary_worktree(&args.against)?;
+            build(worktree.path(), &args.host, &args.against)?
+        };
+        let new = build(&root, &args.host, "the working tree")?;
+
+        debug!("Diffing closures {old} & {new}");
+        let output = Command::new("nix")
+            .args(["store", "diff-closures", &old, &new])
+            .current_dir(&root)
+            .output()?;
+        if !output.status.success() {
+            println!("{}", failure_summary("nix store diff-closures", &output).dimmed());
+            return Err(crate::Error::external("nix store diff-closures", output.status));
+        }
+
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        let changes: Vec<ClosureChange> = stdout.lines().filter_map(ClosureChange::parse).collect();
+        println!();
+        if changes.is_empty() {
+            println!("{}", format!("No package changes in {} since {}.", args.host, args.against).italic());
+            return Ok(());
+        }
+
+        println!(
+            "Changes to {} between {} & the working tree:",
+            args.host.bright_white().bold(),
+            args.against.italic()
+        );
+        for change in changes.iter() {
+            change.print();
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn change<'a>(name: &'a str, versions: Option<(&'a str, &'a str)>, size: Option<&'a str>) -> ClosureChange<'a> {
+        ClosureChange { name, versions, size }
+    }
+
+    #[test]
+    fn parses_diff_closures_lines() {
+        assert_eq!(
+            ClosureChange::parse("firefox: 120.0 → 121.0, +5.2 MiB"),
+            Some(change("firefox", Some(("120.0", "121.0")), Some("+5.2 MiB")))
+        );
+        assert_eq!(
+            ClosureChange::parse("python3: 3.11.6 → ∅, -98765.4 KiB"),
+            Some(change("python3", Some(("3.11.6", ABSENT)), Some("-98765.4 KiB")))
+        );
+        assert_eq!(
+            ClosureChange::parse("zlib: 1.3 → 1.3.1"),
+            Some(change("zlib", Some(("1.3", "1.3.1")), None))
+        );
+        assert_eq!(
+            ClosureChange::parse("nixos-system-web: +3.1 KiB"),
+            Some(change("nixos-system-web", None, Some("+3.1 KiB")))
+        );
+        assert_eq!(ClosureChange::parse("not a change"), None);
+    }
+
+    #[test]
+    fn parses_several_versions() {
+        assert_eq!(
+            ClosureChange::parse("perl: 5.36.0, 5.36.0-env → 5.38.0, +12.0 KiB"),
+            Some(change("perl", Some(("5.36.0, 5.36.0-env", "5.38.0")), Some("+12.0 KiB")))
+        );
+        assert_eq!(
+            ClosureChange::parse("libfoo: ∅ → 1.0, 1.1"),
+            Some(change("libfoo", Some((ABSENT, "1.0, 1.1")), None))
+        );
+        assert_eq!(
+            ClosureChange::parse("openssl: 3.0.12, 1.1.1w → 3.0.13, 1.1.1w, -1.5 KiB"),
+            Some(change("openssl", Some(("3.0.12, 1.1.1w", "3.0.13, 1.1.1w")), Some("-1.5 KiB")))
+        );
+    }
+}
@@ -0,0 +1,118 @@
+use clap::error::ErrorKind;
+use colored::Colorize;
+
+use crate::{
+    cli::{FlakeAddArgs, FlakeArgs, FlakeListArgs, FlakeOperations, FlakeRemoveArgs},
+    config::{ExtraFlake, RESERVED_INPUTS, parse_attr_path, valid_ident},
+    context::Context,
+    dispatch::{Dispatcher, save_project},
+};
+
+fn add(context: Context, args: FlakeAddArgs) -> crate::Result<()> {
+    let mut config = context.config().unwrap();
+
+    if !valid_ident(&args.ident) {
+        return Err(context.error(
+            ErrorKind::ValueValidation,
+            format!("{} is not a valid nix identifier (nix keywords can't be used as input names).", args.ident),
+        ));
+    }
+
+    if RESERVED_INPUTS.contains(&args.ident.as_str())
+        || config.resources.extra_flakes.iter().any(|f| f.ident == args.ident)
+    {
+        return Err(context.error(
+            ErrorKind::ValueValidation,
+            format!("An input named {} already exists.", args.ident),
+        ));
+    }
+
+    let follows = if args.no_follows {
+        None
+    } else if valid_ident(&args.follows) {
+        Some(args.follows)
+    } else {
+        return Err(context.error(
+            ErrorKind::ValueValidation,
+            format!("{} is not a valid nix identifier.", args.follows),
+        ));
+    };
+
+    config.resources.extra_flakes.push(ExtraFlake {
+        ident: args.ident.clone(),
+        url: args.url,
+        follows,
+    });
+
+    save_project(context, &config)?;
+    println!("Added flake input {}", args.ident.bright_white().bold());
+    Ok(())
+}
+
+fn remove(context: Context, args: FlakeRemoveArgs) -> crate::Result<()> {
+    let mut config = context.config().unwrap();
+
+    let before = config.resources.extra_flakes.len();
+    config.resources.extra_flakes.retain(|f| f.ident != args.ident);
+    if config.resources.extra_flakes.len() == before {
+        return Err(context.error(
+            ErrorKind::ValueValidation,
+            format!("No extra flake named {} exists.", args.ident),
+        ));
+    }
+
+    // Dev packages can refer to an input directly (`foo.packages...`) or through `inputs.foo...`
+    let dependents: Vec<&String> = config
+        .resources
+        .dev_packages
+        .iter()
+        .filter(|package| {
+            parse_attr_path(package).is_some_and(|segments| match segments.as_slice() {
+                [first, ..] if *first == args.ident => true,
+                [first, second, ..] => first == "inputs" && *second == args.ident,
+                _ => false,
+            })
+        })
+        .collect();
+    if !dependents.is_empty() {
+        return Err(context.error(
+            ErrorKind::ValueValidation,
+            format!(
+                "{} is still used by devshell packages: {}. Remove them with `nico devshell remove` first.",
+                args.ident,
+                dependents.iter().map(|p| p.as_str()).collect::<Vec<_>>().join(", ")
+            ),
+        ));
+    }
+
+    save_project(context, &config)?;
+    println!("Removed flake input {}", args.ident.bright_white().bold());
+    Ok(())
+}
+
+fn list(context: Context, _: FlakeListArgs) -> crate::Result<()> {
+    let config = context.config().unwrap();
+    if config.resources.extra_flakes.is_empty() {
+        println!("{}", "No extra flakes configured.".italic());
+    }
+
+    for ExtraFlake { ident, url, follows } in config.resources.extra_flakes {
+        let follows = follows
+            .map(|f| format!(" (follows {f})"))
+            .unwrap_or_default();
+        println!("  - {}: {}{}", ident.bright_white().bold(), url.italic(), follows.dimmed());
+    }
+    Ok(())
+}
+
+pub struct FlakeDispatcher;
+impl Dispatcher for FlakeDispatcher {
+    type Args = FlakeArgs;
+    fn dispatch(context: Context, args: Self::Args) -> crate::Result<()> {
+        match args.operation {
+            FlakeOperations::Add(args) => add(context, args),
+            FlakeOperations::Remove(args) => remove(context, args),
+            FlakeOperations::List(args) => list(context, args),
+        }
+    }
+}
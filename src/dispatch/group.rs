@@ -0,0 +1,106 @@
+use std::fs;
+
+use clap::error::ErrorKind;
+use colored::Colorize;
+use log::*;
+
+use crate::{
+    cli::{GroupAddArgs, GroupArgs, GroupListArgs, GroupOperations, GroupRemoveArgs},
+    config::{Host, SecretScope, group_module},
+    context::Context,
+    dispatch::{Dispatcher, save_project},
+    repo::RepoExt,
+};
+
+fn add(context: Context, args: GroupAddArgs) -> crate::Result<()> {
+    let mut config = context.config().unwrap();
+    if !Host::valid_name(&args.name) {
+        return Err(context.error(
+            ErrorKind::ValueValidation,
+            "Group names must start with a letter and only contain letters, numbers, '-' and '_'.",
+        ));
+    }
+
+    if !config.groups.insert(args.name.clone()) {
+        return Err(context.error(
+            ErrorKind::ValueValidation,
+            format!("A group named {} already exists.", args.name),
+        ));
+    }
+
+    save_project(context, &config)?;
+    println!(
+        "Added group {} ({})",
+        args.name.bright_white().bold(),
+        group_module(&args.name).to_str().unwrap().italic()
+    );
+    Ok(())
+}
+
+fn remove(context: Context, args: GroupRemoveArgs) -> crate::Result<()> {
+    let root = context.project_root().unwrap();
+    let mut config = context.config().unwrap();
+    if !config.host_groups().contains(&args.name) {
+        return Err(context.error(
+            ErrorKind::ValueValidation,
+            format!("No group named {} exists.", args.name),
+        ));
+    }
+
+    config.groups.remove(&args.name);
+    for host in config.hosts.values_mut().filter(|h| h.tags.contains(&args.name)) {
+        debug!("Removing {} from {}", host.name, args.name);
+        host.tags.retain(|t| t != &args.name);
+    }
+
+    let scope = SecretScope::Group(args.name.clone());
+    if config.secrets.files.iter().any(|s| s.scope == scope) {
+        warn!("{} still has secrets, which only admins will be able to decrypt now.", args.name);
+    }
+
+    save_project(context.clone(), &config)?;
+    let module = group_module(&args.name);
+    if args.purge && root.join(&module).exists() {
+        debug!("Deleting {module:?}");
+        fs::remove_file(root.join(&module))?;
+        context.repository()?.add_files([module.to_str().unwrap()])?;
+    }
+    println!("Removed group {}", args.name.bright_white().bold());
+    Ok(())
+}
+
+fn list(context: Context, _: GroupListArgs) -> crate::Result<()> {
+    let config = context.config().unwrap();
+    let groups = config.host_groups();
+    if groups.is_empty() {
+        println!("{}", "No groups configured.".italic());
+    }
+
+    for group in groups {
+        let hosts: Vec<&str> = config
+            .hosts
+            .values()
+            .filter(|h| h.tags.contains(&group))
+            .map(|h| h.name.as_str())
+            .collect();
+        let hosts = if hosts.is_empty() {
+            "no hosts".dimmed().to_string()
+        } else {
+            hosts.join(", ").italic().to_string()
+        };
+        println!("  - {}: {hosts}", group.bright_white().bold());
+    }
+    Ok(())
+}
+
+pub struct GroupDispatcher;
+impl Dispatcher for GroupDispatcher {
+    type Args = GroupArgs;
+    fn dispatch(context: Context, args: Self::Args) -> crate::Result<()> {
+        match args.operation {
+            GroupOperations::Add(args) => add(context, args),
+            GroupOperations::Remove(args) => remove(context, args),
+            GroupOperations::List(args) => list(context, args),
+        }
+    }
+}
@@ -0,0 +1,252 @@
+use clap::error::ErrorKind;
+use colored::Colorize;
+use log::*;
+use std::{fs, io::Read};
+
+use crate::{
+    cli::{
+        HostAddArgs, HostArgs, HostKeyArgs, HostKeyImportArgs, HostKeyOperations, HostKeySetArgs,
+        HostListArgs, HostOperations, HostRemoveArgs, HostTagArgs, HostTargetArgs,
+    },
+    config::{DeployTarget, Host, SecretScope, valid_recipient},
+    context::Context,
+    dispatch::{Dispatcher, save_project},
+    repo::RepoExt,
+    secrets::find_ssh_recipient,
+};
+
+fn add(context: Context, args: HostAddArgs) -> crate::Result<()> {
+    let root = context.project_root().unwrap();
+    let mut config = context.config().unwrap();
+
+    if !Host::valid_name(&args.name) {
+        return Err(context.error(
+            ErrorKind::ValueValidation,
+            "Host names must start with a letter and only contain letters, numbers, '-' and '_'.",
+        ));
+    }
+
+    if let Some(tag) = args.tags.iter().find(|t| !Host::valid_name(t)) {
+        return Err(context.error(
+            ErrorKind::ValueValidation,
+            format!("{tag} is not a valid tag (tags follow the same rules as host names)."),
+        ));
+    }
+
+    if let Some(key) = args.age_key.as_ref().filter(|k| !valid_recipient(k)) {
+        return Err(context.error(
+            ErrorKind::ValueValidation,
+            format!("{key} is not a valid age recipient."),
+        ));
+    }
+
+    if config.hosts.contains_key(&args.name) {
+        return Err(context.error(
+            ErrorKind::ValueValidation,
+            format!("A host named {} already exists.", args.name),
+        ));
+    }
+
+    let host = Host {
+        name: args.name.clone(),
+        system: args.system.unwrap_or(config.init.system.clone()),
+        tags: args.tags,
+        age_key: args.age_key,
+        deploy: None,
+    };
+
+    config.groups.extend(host.tags.iter().cloned());
+    config.hosts.insert(host.name.clone(), host.clone());
+    // Saving first means nothing is scaffolded if the project's files can't be merged
+    save_project(context.clone(), &config)?;
+
+    let host_file = root.join(host.directory()).join("default.nix");
+    if host_file.exists() {
+        info!("{host_file:?} already exists, leaving it as-is.");
+    } else {
+        debug!("Writing {host_file:?}");
+        fs::create_dir_all(root.join(host.directory()))?;
+        fs::write(&host_file, context.render_template("host/default.nix", &host)?)?;
+    }
+    context
+        .repository()?
+        .add_files([host.directory().to_str().unwrap()])?;
+    println!("Added host {}", host.name.bright_white().bold());
+    Ok(())
+}
+
+fn remove(context: Context, args: HostRemoveArgs) -> crate::Result<()> {
+    let root = context.project_root().unwrap();
+    let mut config = context.config().unwrap();
+
+    let Some(host) = config.hosts.remove(&args.name) else {
+        return Err(context.error(
+            ErrorKind::ValueValidation,
+            format!("No host named {} exists.", args.name),
+        ));
+    };
+
+    let scope = SecretScope::Host(host.name.clone());
+    if config.secrets.files.iter().any(|s| s.scope == scope) {
+        warn!("{} still has secrets, which only admins will be able to decrypt now.", host.name);
+    }
+
+    save_project(context.clone(), &config)?;
+    if args.purge && root.join(host.directory()).exists() {
+        debug!("Deleting {:?}", host.directory());
+        fs::remove_dir_all(root.join(host.directory()))?;
+        context
+            .repository()?
+            .add_files([host.directory().to_str().unwrap()])?;
+    }
+    println!("Removed host {}", host.name.bright_white().bold());
+    Ok(())
+}
+
+fn list(context: Context, _: HostListArgs) -> crate::Result<()> {
+    let config = context.config().unwrap();
+    if config.hosts.is_empty() {
+        println!("{}", "No hosts configured.".italic());
+    }
+
+    for Host { name, system, tags, deploy, .. } in config.hosts.values() {
+        let tags = if tags.is_empty() {
+            String::new()
+        } else {
+            format!(" [{}]", tags.join(", "))
+        };
+        let deploy = deploy.as_ref().map(|t| format!(" -> {}", t.destination())).unwrap_or_default();
+        println!("  - {}: {}{}{}", name.bright_white().bold(), system.italic(), tags.dimmed(), deploy.dimmed());
+    }
+    Ok(())
+}
+
+fn set_key(context: Context, host: String, recipient: String) -> crate::Result<()> {
+    let mut config = context.config().unwrap();
+    let Some(entry) = config.hosts.get_mut(&host) else {
+        return Err(context.error(
+            ErrorKind::ValueValidation,
+            format!("No host named {host} exists."),
+        ));
+    };
+
+    entry.age_key = Some(recipient.clone());
+    save_project(context, &config)?;
+    println!("Set age key of {} to {}", host.bright_white().bold(), recipient.italic());
+    println!("Run `nico secrets rekey` to re-encrypt existing secrets for it.");
+    Ok(())
+}
+
+fn key_import(context: Context, args: HostKeyImportArgs) -> crate::Result<()> {
+    let source = if args.source == "-" {
+        let mut buffer = String::new();
+        std::io::stdin().read_to_string(&mut buffer)?;
+        buffer
+    } else {
+        fs::read_to_string(&args.source)?
+    };
+
+    match find_ssh_recipient(source) {
+        Some(recipient) => set_key(context, args.host, recipient),
+        None => Err(context.error(
+            ErrorKind::ValueValidation,
+            format!("No valid ssh-ed25519 public key found in {}.", args.source),
+        )),
+    }
+}
+
+fn key_set(context: Context, args: HostKeySetArgs) -> crate::Result<()> {
+    if !valid_recipient(&args.recipient) {
+        return Err(context.error(
+            ErrorKind::ValueValidation,
+            format!("{} is not a valid age recipient.", args.recipient),
+        ));
+    }
+
+    set_key(context, args.host, args.recipient)
+}
+
+fn tag(context: Context, args: HostTagArgs) -> crate::Result<()> {
+    let mut config = context.config().unwrap();
+    if let Some(tag) = args.tags.iter().find(|t| !Host::valid_name(t)) {
+        return Err(context.error(
+            ErrorKind::ValueValidation,
+            format!("{tag} is not a valid tag (tags follow the same rules as host names)."),
+        ));
+    }
+
+    let Some(host) = config.hosts.get_mut(&args.host) else {
+        return Err(context.error(
+            ErrorKind::ValueValidation,
+            format!("No host named {} exists.", args.host),
+        ));
+    };
+
+    for tag in args.tags.iter() {
+        if args.remove {
+            if !host.tags.contains(tag) {
+                info!("{} isn't tagged with {tag}, skipping.", host.name);
+                continue;
+            }
+            host.tags.retain(|t| t != tag);
+            println!("Removed {} from {}", host.name.bright_white().bold(), tag.italic());
+        } else {
+            if host.tags.contains(tag) {
+                info!("{} is already tagged with {tag}, skipping.", host.name);
+                continue;
+            }
+            host.tags.push(tag.clone());
+            println!("Added {} to {}", host.name.bright_white().bold(), tag.italic());
+        }
+    }
+
+    if !args.remove {
+        config.groups.extend(args.tags);
+    }
+    save_project(context, &config)?;
+    println!("Run `nico secrets rekey` to update which secrets the host can decrypt.");
+    Ok(())
+}
+
+fn target(context: Context, args: HostTargetArgs) -> crate::Result<()> {
+    let mut config = context.config().unwrap();
+    let Some(host) = config.hosts.get_mut(&args.host) else {
+        return Err(context.error(
+            ErrorKind::ValueValidation,
+            format!("No host named {} exists.", args.host),
+        ));
+    };
+
+    host.deploy = args.address.map(|address| DeployTarget {
+        address,
+        user: args.user,
+        sudo: args.sudo,
+        build_on_target: args.build_on_target,
+    });
+    let summary = match &host.deploy {
+        Some(target) => format!("Set deploy target of {} to {}", args.host.bright_white().bold(), target.destination().italic()),
+        None => format!("Removed deploy target of {}", args.host.bright_white().bold()),
+    };
+
+    save_project(context, &config)?;
+    println!("{summary}");
+    Ok(())
+}
+
+pub struct HostDispatcher;
+impl Dispatcher for HostDispatcher {
+    type Args = HostArgs;
+    fn dispatch(context: Context, args: Self::Args) -> crate::Result<()> {
+        match args.operation {
+            HostOperations::Add(args) => add(context, args),
+            HostOperations::Remove(args) => remove(context, args),
+            HostOperations::List(args) => list(context, args),
+            HostOperations::Key(HostKeyArgs { operation }) => match operation {
+                HostKeyOperations::Import(args) => key_import(context, args),
+                HostKeyOperations::Set(args) => key_set(context, args),
+            },
+            HostOperations::Tag(args) => tag(context, args),
+            HostOperations::Target(args) => target(context, args),
+        }
+    }
+}
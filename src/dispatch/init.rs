@@ -3,20 +3,23 @@ use crate::{
     config::{Configuration, GitRemote},
     context::Context,
     dispatch::Dispatcher, repo::RepoExt,
+    secrets::{ensure_identity, sops_key_file},
 };
 use clap::error::ErrorKind;
 use git2::{
     FetchOptions, Repository,
     build::{CheckoutBuilder, RepoBuilder},
 };
+use colored::Colorize;
+use inquire::Confirm;
 use log::*;
-use std::{fs, path::PathBuf, process::Command};
+use std::{fs, io::IsTerminal, path::PathBuf, process::Command};
 
 fn directory_setup(context: Context, args: InitArgs) -> crate::Result<(PathBuf, Vec<GitRemote>, Repository)> {
     let target_folder = args
         .clone()
         .path
-        .and_then(|v| Some(PathBuf::from(v)))
+        .map(PathBuf::from)
         .unwrap_or(std::env::current_dir()?);
 
     info!("Initializing a project at {target_folder:?}");
@@ -79,20 +82,18 @@ fn directory_setup(context: Context, args: InitArgs) -> crate::Result<(PathBuf,
         for remote_name in repo
             .remotes()?
             .into_iter()
-            .filter_map(|v| v.and_then(|s| Some(s.to_string())))
+            .filter_map(|v| v.map(|s| s.to_string()))
         {
-            if let Ok(remote) = repo.find_remote(&remote_name) {
-                if let Some(name) = remote.name() {
-                    if let Some(url) = remote.url() {
-                        if let Ok(branch) = remote.default_branch() {
-                            remotes.push(
-                                GitRemote::builder(name, url)
-                                    .main_branch(branch.as_str().unwrap_or("main"))
-                                    .build(),
-                            );
-                        }
-                    }
-                }
+            if let Ok(remote) = repo.find_remote(&remote_name)
+                && let Some(name) = remote.name()
+                && let Some(url) = remote.url()
+                && let Ok(branch) = remote.default_branch()
+            {
+                remotes.push(
+                    GitRemote::builder(name, url)
+                        .main_branch(branch.as_str().unwrap_or("main"))
+                        .build(),
+                );
             }
         }
 
@@ -100,23 +101,62 @@ fn directory_setup(context: Context, args: InitArgs) -> crate::Result<(PathBuf,
     }
 }
 
+/// Sets up the admin age key (if requested), returning the admin's name and recipient.
+fn admin_setup(args: &InitArgs) -> crate::Result<Option<(String, String)>> {
+    let wanted = if args.generate_age_key {
+        true
+    } else if args.no_age_key || !std::io::stdin().is_terminal() {
+        false
+    } else {
+        Confirm::new("Set up an admin age key for secrets?")
+            .with_default(true)
+            .prompt()
+            .map_err(anyhow::Error::from)?
+    };
+
+    if !wanted {
+        return Ok(None);
+    }
+
+    let Some(key_file) = sops_key_file() else {
+        warn!("Unable to determine the sops key location, skipping admin key setup.");
+        return Ok(None);
+    };
+
+    let (recipient, generated) = ensure_identity(&key_file)?;
+    if generated {
+        println!("Generated a new age identity in {}", key_file.to_str().unwrap().italic());
+    } else {
+        println!("Using the existing age identity in {}", key_file.to_str().unwrap().italic());
+    }
+
+    let name = args
+        .admin_name
+        .clone()
+        .or(std::env::var("USER").ok())
+        .unwrap_or(String::from("admin"));
+    Ok(Some((name, recipient)))
+}
+
 pub struct InitDispatcher;
 impl Dispatcher for InitDispatcher {
     type Args = InitArgs;
     fn dispatch(context: Context, args: Self::Args) -> crate::Result<()> {
         let (target_folder, remotes, repo) = directory_setup(context.clone(), args.clone())?;
+        let admin = admin_setup(&args)?;
 
         debug!(
             "Writing configuration to {:?}",
-            target_folder.join("nico.config.json")
+            target_folder.join(args.config_format.file_name())
         );
-        let config = Configuration::new(target_folder.clone(), args, remotes)?;
+        let mut config = Configuration::new(target_folder.clone(), args, remotes)?;
+        if let Some((name, recipient)) = admin {
+            debug!("Registering {recipient} as admin {name}");
+            config.secrets.admins.insert(name, recipient);
+        }
         trace!("Config data: {config:?}");
         debug!("Writing flake.nix.");
-
-        let rendered = config.render_flake(context.clone())?;
-        fs::write(target_folder.join("flake.nix"), rendered)?;
-        fs::write(target_folder.join(".envrc"), "use flake")?;
+        config.write_files(context.clone(), &target_folder)?;
 
         repo.add_files(["."])?;
         repo.create_commit("Nico initialization")?;
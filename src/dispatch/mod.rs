@@ -1,18 +1,55 @@
-use std::fmt::Debug;
+use std::{fmt::Debug, path::PathBuf, process::Output};
 
 use clap::Args;
 use serde::{Serialize, de::DeserializeOwned};
 
-use crate::{cli::Operations, context::Context};
+use crate::{cli::Operations, config::Configuration, context::Context, repo::RepoExt};
 
 pub trait Dispatcher {
     type Args: Serialize + DeserializeOwned + Clone + Debug + Args;
     fn dispatch(context: Context, args: Self::Args) -> crate::Result<()>;
 }
 
+/// Writes the config & all managed files into the project, then stages them so the flake can see them. Returns the
+/// paths that were written.
+pub(crate) fn save_project(context: Context, config: &Configuration) -> crate::Result<Vec<PathBuf>> {
+    let root = context.project_root().unwrap();
+    config.save(&root)?;
+    let mut written = vec![PathBuf::from(config.format.file_name())];
+    written.extend(config.write_files(context.clone(), &root)?);
+    context
+        .repository()?
+        .add_files(written.iter().map(|p| p.to_str().unwrap()))?;
+    Ok(written)
+}
+
+/// Summarizes why an external command failed, as the last few lines of its stderr.
+pub(crate) fn failure_summary(command: &str, output: &Output) -> String {
+    const TAIL: usize = 10;
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    let lines: Vec<&str> = stderr.lines().collect();
+    if lines.is_empty() {
+        format!("{command} exited with {}", output.status)
+    } else {
+        lines[lines.len().saturating_sub(TAIL)..].join("\n")
+    }
+}
+
+mod build;
 mod completions;
+mod config;
+mod deploy;
+mod devshell;
+mod diff;
+mod flake;
+mod group;
+mod host;
 mod init;
+mod regen;
+mod remote;
+mod secrets;
 mod status;
+mod templates;
 
 pub fn dispatch(context: Context) -> crate::Result<()> {
     match context.operation.clone() {
@@ -20,6 +57,18 @@ pub fn dispatch(context: Context) -> crate::Result<()> {
             completions::CompletionsDispatcher::dispatch(context, args)
         }
         Operations::Init(args) => init::InitDispatcher::dispatch(context, args),
-        Operations::Status(args) => status::StatusDispatcher::dispatch(context, args)
+        Operations::Status(args) => status::StatusDispatcher::dispatch(context, args),
+        Operations::Host(args) => host::HostDispatcher::dispatch(context, args),
+        Operations::Group(args) => group::GroupDispatcher::dispatch(context, args),
+        Operations::Flake(args) => flake::FlakeDispatcher::dispatch(context, args),
+        Operations::Devshell(args) => devshell::DevshellDispatcher::dispatch(context, args),
+        Operations::Remote(args) => remote::RemoteDispatcher::dispatch(context, args),
+        Operations::Secrets(args) => secrets::SecretsDispatcher::dispatch(context, args),
+        Operations::Build(args) => build::BuildDispatcher::dispatch(context, args),
+        Operations::Deploy(args) => deploy::DeployDispatcher::dispatch(context, args),
+        Operations::Diff(args) => diff::DiffDispatcher::dispatch(context, args),
+        Operations::Regen(args) => regen::RegenDispatcher::dispatch(context, args),
+        Operations::Templates(args) => templates::TemplatesDispatcher::dispatch(context, args),
+        Operations::Config(args) => config::ConfigDispatcher::dispatch(context, args),
     }
 }
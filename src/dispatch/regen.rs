@@ -0,0 +1,72 @@
+use std::{fs, io::IsTerminal, path::PathBuf};
+
+use colored::Colorize;
+use inquire::Confirm;
+use similar::{ChangeTag, TextDiff};
+
+use crate::{cli::RegenArgs, context::Context, dispatch::Dispatcher, repo::RepoExt};
+
+/// Prints a colored unified diff between the current & regenerated content of a file.
+fn print_diff(path: &str, current: &str, generated: &str) {
+    println!("{}", format!("--- a/{path}").red().bold());
+    println!("{}", format!("+++ b/{path}").green().bold());
+
+    let diff = TextDiff::from_lines(current, generated);
+    for hunk in diff.unified_diff().context_radius(3).iter_hunks() {
+        println!("{}", hunk.header().to_string().cyan());
+        for change in hunk.iter_changes() {
+            let line = change.to_string_lossy();
+            let line = line.trim_end_matches('\n');
+            match change.tag() {
+                ChangeTag::Delete => println!("{}", format!("-{line}").red()),
+                ChangeTag::Insert => println!("{}", format!("+{line}").green()),
+                ChangeTag::Equal => println!(" {line}"),
+            }
+        }
+    }
+}
+
+pub struct RegenDispatcher;
+impl Dispatcher for RegenDispatcher {
+    type Args = RegenArgs;
+    fn dispatch(context: Context, args: Self::Args) -> crate::Result<()> {
+        let root = context.project_root().unwrap();
+        let config = context.config().unwrap();
+
+        let mut stale: Vec<PathBuf> = vec![];
+        for (path, generated) in config.project_files(context.clone(), &root)? {
+            let current = fs::read_to_string(root.join(&path)).unwrap_or_default();
+            if current != generated {
+                print_diff(path.to_str().unwrap(), &current, &generated);
+                stale.push(path);
+            }
+        }
+
+        if stale.is_empty() {
+            println!("{}", "All generated files are up to date.".italic());
+            return Ok(());
+        }
+        if args.check {
+            return Err(crate::Error::StaleFiles(stale));
+        }
+
+        let confirmed = args.yes
+            || (std::io::stdin().is_terminal()
+                && Confirm::new(&format!("Write {} file(s)?", stale.len()))
+                    .with_default(true)
+                    .prompt()
+                    .map_err(anyhow::Error::from)?);
+        if !confirmed {
+            println!("{}", "Nothing was written (use --yes to write without confirmation).".italic());
+            return Ok(());
+        }
+
+        // The config is left alone (unless it needs migrating), so comments in it survive
+        let written = config.write_files(context.clone(), &root)?;
+        context
+            .repository()?
+            .add_files(written.iter().map(|p| p.to_str().unwrap()))?;
+        println!("Regenerated {} file(s)", stale.len().to_string().bright_white().bold());
+        Ok(())
+    }
+}
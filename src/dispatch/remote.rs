@@ -0,0 +1,210 @@
+use std::collections::BTreeMap;
+
+use clap::error::ErrorKind;
+use colored::Colorize;
+use git2::Repository;
+use log::*;
+
+use crate::{
+    cli::{
+        RemoteAddArgs, RemoteArgs, RemoteListArgs, RemoteOperations, RemoteRemoveArgs,
+        RemoteSetArgs, RemoteSource, RemoteSyncArgs,
+    },
+    config::GitRemote,
+    context::Context,
+    dispatch::{Dispatcher, save_project},
+};
+
+fn git_remotes(repo: &Repository) -> crate::Result<BTreeMap<String, String>> {
+    Ok(repo
+        .remotes()?
+        .iter()
+        .flatten()
+        .filter_map(|name| {
+            repo.find_remote(name)
+                .ok()
+                .and_then(|r| r.url().map(|url| (name.to_string(), url.to_string())))
+        })
+        .collect())
+}
+
+fn add(context: Context, args: RemoteAddArgs) -> crate::Result<()> {
+    let mut config = context.config().unwrap();
+    if config.resources.remotes.contains_key(&args.name) {
+        return Err(context.error(
+            ErrorKind::ValueValidation,
+            format!("A remote named {} already exists.", args.name),
+        ));
+    }
+
+    if args.git {
+        debug!("Adding {} to the git repository", args.name);
+        context.repository()?.remote(&args.name, &args.url)?;
+    }
+
+    let remote = GitRemote::builder(args.name.clone(), args.url)
+        .main_branch(args.main_branch)
+        .testing_branch_prefix(args.testing_branch_prefix)
+        .polling_period(args.polling_period)
+        .timeout(args.timeout)
+        .build();
+    config.resources.remotes.insert(args.name.clone(), remote);
+    save_project(context, &config)?;
+
+    println!("Added remote {}", args.name.bright_white().bold());
+    Ok(())
+}
+
+fn remove(context: Context, args: RemoteRemoveArgs) -> crate::Result<()> {
+    let mut config = context.config().unwrap();
+    if config.resources.remotes.remove(&args.name).is_none() {
+        return Err(context.error(
+            ErrorKind::ValueValidation,
+            format!("No remote named {} exists.", args.name),
+        ));
+    }
+
+    if args.git {
+        let repo = context.repository()?;
+        if repo.find_remote(&args.name).is_ok() {
+            debug!("Removing {} from the git repository", args.name);
+            repo.remote_delete(&args.name)?;
+        }
+    }
+    save_project(context, &config)?;
+
+    println!("Removed remote {}", args.name.bright_white().bold());
+    Ok(())
+}
+
+fn set(context: Context, args: RemoteSetArgs) -> crate::Result<()> {
+    let mut config = context.config().unwrap();
+    let Some(remote) = config.resources.remotes.get_mut(&args.name) else {
+        return Err(context.error(
+            ErrorKind::ValueValidation,
+            format!("No remote named {} exists.", args.name),
+        ));
+    };
+
+    if let Some(url) = args.url {
+        remote.url = url;
+    }
+    if let Some(main_branch) = args.main_branch {
+        remote.main_branch = main_branch;
+    }
+    if let Some(prefix) = args.testing_branch_prefix {
+        remote.testing_branch_prefix = prefix;
+    }
+    if let Some(period) = args.polling_period {
+        remote.polling_period = period;
+    }
+    if let Some(timeout) = args.timeout {
+        remote.timeout = timeout;
+    }
+
+    if args.git {
+        let repo = context.repository()?;
+        if repo.find_remote(&remote.name).is_ok() {
+            repo.remote_set_url(&remote.name, &remote.url)?;
+        } else {
+            repo.remote(&remote.name, &remote.url)?;
+        }
+    }
+    save_project(context, &config)?;
+
+    println!("Updated remote {}", args.name.bright_white().bold());
+    Ok(())
+}
+
+fn list(context: Context, _: RemoteListArgs) -> crate::Result<()> {
+    let config = context.config().unwrap();
+    let git = git_remotes(&context.repository()?)?;
+    if config.resources.remotes.is_empty() {
+        println!("{}", "No remotes configured.".italic());
+    }
+
+    let remotes: BTreeMap<String, GitRemote> = config.resources.remotes.into_iter().collect();
+    for (name, remote) in remotes {
+        let drift = match git.get(&name) {
+            Some(url) if url == &remote.url => String::new(),
+            Some(url) => format!(" (git: {url})").yellow().to_string(),
+            None => " (not in git)".dimmed().to_string(),
+        };
+        println!("  - {}: {}{drift}", name.bright_white().bold(), remote.url.italic());
+        println!(
+            "      branches: {} / {}<host>, polling every {}s, timeout {}s",
+            remote.main_branch, remote.testing_branch_prefix, remote.polling_period, remote.timeout
+        );
+    }
+    Ok(())
+}
+
+fn sync(context: Context, args: RemoteSyncArgs) -> crate::Result<()> {
+    let repo = context.repository()?;
+    let mut config = context.config().unwrap();
+    let git = git_remotes(&repo)?;
+    let mut config_changed = false;
+    let mut in_sync = true;
+
+    for (name, url) in git.iter() {
+        match config.resources.remotes.get_mut(name) {
+            None => {
+                println!("{} {name} ({url}) {}", "+".green().bold(), "config <- git".dimmed());
+                config
+                    .resources
+                    .remotes
+                    .insert(name.clone(), GitRemote::builder(name, url).build());
+                config_changed = true;
+                in_sync = false;
+            }
+            Some(remote) if &remote.url != url => {
+                in_sync = false;
+                match args.prefer {
+                    RemoteSource::Config => {
+                        println!("{} {name}: {url} -> {} {}", "~".yellow().bold(), remote.url, "git <- config".dimmed());
+                        if !args.dry_run {
+                            repo.remote_set_url(name, &remote.url)?;
+                        }
+                    }
+                    RemoteSource::Git => {
+                        println!("{} {name}: {} -> {url} {}", "~".yellow().bold(), remote.url, "config <- git".dimmed());
+                        remote.url = url.clone();
+                        config_changed = true;
+                    }
+                }
+            }
+            _ => (),
+        }
+    }
+
+    for remote in config.resources.remotes.values() {
+        if !git.contains_key(&remote.name) {
+            println!("{} {} ({}) {}", "+".green().bold(), remote.name, remote.url, "git <- config".dimmed());
+            in_sync = false;
+            if !args.dry_run {
+                repo.remote(&remote.name, &remote.url)?;
+            }
+        }
+    }
+
+    if in_sync {
+        println!("{}", "Remotes are already in sync.".italic());
+    } else if config_changed && !args.dry_run {
+        save_project(context, &config)?;
+    }
+    Ok(())
+}
+
+pub struct RemoteDispatcher;
+impl Dispatcher for RemoteDispatcher {
+    type Args = RemoteArgs;
+    fn dispatch(context: Context, args: Self::Args) -> crate::Result<()> {
+        match args.operation {
+            RemoteOperations::Add(args) => add(context, args),
+            RemoteOperations::Remove(args) => remove(context, args),
+            RemoteOperations::Set(args) => set(context, args),
+            RemoteOperations::List(args) => list(context, args),
+            RemoteOperations::Sync(args) => sync(context, args),
+        }
+    }
+}
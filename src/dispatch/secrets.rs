@@ -0,0 +1,393 @@
+use std::{
+    collections::BTreeMap,
+    fs,
+    io::{IsTerminal, Read},
+};
+
+use clap::error::ErrorKind;
+use colored::Colorize;
+use inquire::{Password, PasswordDisplayMode};
+use log::*;
+
+use crate::{
+    cli::{
+        SecretsAddArgs, SecretsAdminAddArgs, SecretsAuditArgs, SecretsAdminArgs, SecretsAdminListArgs,
+        SecretsAdminOperations, SecretsAdminRemoveArgs, SecretsArgs, SecretsEditArgs,
+        SecretsGetArgs, SecretsOperations, SecretsRekeyArgs, SecretsSetArgs,
+    },
+    config::{Configuration, Host, Secret, SecretScope, valid_recipient},
+    context::Context,
+    dispatch::{Dispatcher, save_project},
+    repo::RepoExt,
+    secrets::{AuditIssue, SopsMetadata, audit, ensure_sops, sops, sops_with_input},
+};
+
+/// Parses & validates a `<host>`/`@<group>` scope and secret name into a [`Secret`].
+fn resolve_secret(context: &Context, config: &Configuration, scope: &str, name: &str) -> crate::Result<Secret> {
+    let scope = SecretScope::parse(scope);
+    if !Host::valid_name(scope.name()) || !Host::valid_name(name) {
+        return Err(context.error(
+            ErrorKind::ValueValidation,
+            "Secret & scope names must start with a letter and only contain letters, numbers, '-' and '_'.",
+        ));
+    }
+
+    if let SecretScope::Host(host) = &scope
+        && !config.hosts.contains_key(host)
+    {
+        return Err(context.error(
+            ErrorKind::ValueValidation,
+            format!("No host named {host} exists (use @{host} for a group)."),
+        ));
+    }
+
+    Ok(Secret::new(name, scope))
+}
+
+/// Validates the deployment options of a secret, since they're written into nix strings as-is.
+fn validate_options(context: &Context, owner: &Option<String>, group: &Option<String>, mode: &Option<String>, units: &[String]) -> crate::Result<()> {
+    let valid_name = |name: &String| name.chars().all(|c| c.is_ascii_alphanumeric() || "-_.".contains(c));
+    if let Some(name) = owner.iter().chain(group.iter()).find(|n| !valid_name(n)) {
+        return Err(context.error(
+            ErrorKind::ValueValidation,
+            format!("{name} is not a valid user or group name."),
+        ));
+    }
+
+    if let Some(mode) = mode.as_ref().filter(|m| !Secret::valid_mode(m)) {
+        return Err(context.error(
+            ErrorKind::ValueValidation,
+            format!("{mode} is not a valid octal mode (eg. 0440)."),
+        ));
+    }
+
+    if let Some(unit) = units
+        .iter()
+        .find(|u| !u.chars().all(|c| c.is_ascii_alphanumeric() || "-_.@:".contains(c)))
+    {
+        return Err(context.error(
+            ErrorKind::ValueValidation,
+            format!("{unit} is not a valid systemd unit name."),
+        ));
+    }
+    Ok(())
+}
+
+fn read_value(name: &str) -> crate::Result<String> {
+    if std::io::stdin().is_terminal() {
+        Ok(Password::new(&format!("Value of {name}:"))
+            .with_display_mode(PasswordDisplayMode::Masked)
+            .prompt()
+            .map_err(anyhow::Error::from)?)
+    } else {
+        let mut buffer = String::new();
+        std::io::stdin().read_to_string(&mut buffer)?;
+        Ok(buffer.trim_end_matches('\n').to_string())
+    }
+}
+
+fn add(context: Context, args: SecretsAddArgs) -> crate::Result<()> {
+    let root = context.project_root().unwrap();
+    let mut config = context.config().unwrap();
+    let mut secret = resolve_secret(&context, &config, &args.scope, &args.name)?;
+    validate_options(&context, &args.owner, &args.group, &args.mode, &args.restart_units)?;
+    secret.owner = args.owner;
+    secret.group = args.group;
+    secret.mode = args.mode;
+    secret.restart_units = args.restart_units;
+    let path = secret.path();
+
+    if root.join(&path).exists() || config.secrets.files.iter().any(|s| s.path() == path) {
+        return Err(context.error(
+            ErrorKind::ValueValidation,
+            format!("{} already exists, use `nico secrets edit` to change it.", path.to_str().unwrap()),
+        ));
+    }
+
+    let recipients = config.recipients(&secret.scope);
+    if recipients.is_empty() {
+        return Err(context.error(
+            ErrorKind::ValueValidation,
+            format!("Nobody could decrypt a secret for {}, add an admin or a host age key first.", secret.scope),
+        ));
+    }
+    if config.scope_hosts(&secret.scope).iter().all(|h| h.age_key.is_none()) {
+        warn!("No host in {} has an age key yet, only admins will be able to decrypt {path:?}.", secret.scope);
+    }
+
+    let _sops_version = ensure_sops()?;
+    let value = match args.value {
+        Some(value) => value,
+        None => read_value(&secret.name)?,
+    };
+    let plaintext = serde_norway::to_string(&BTreeMap::from([(secret.name.clone(), value)]))?;
+
+    fs::create_dir_all(root.join(secret.scope.directory()))?;
+    // Encrypting stdin under the secret's own name lets the `.sops.yaml` creation rules pick the recipients.
+    sops_with_input(
+        &root,
+        [
+            "--encrypt",
+            "--filename-override",
+            path.to_str().unwrap(),
+            "--input-type",
+            "yaml",
+            "--output-type",
+            "yaml",
+            "--output",
+            path.to_str().unwrap(),
+            "/dev/stdin",
+        ],
+        Some(plaintext),
+    )?;
+
+    config.secrets.files.push(secret.clone());
+    save_project(context.clone(), &config)?;
+    context.repository()?.add_files([path.to_str().unwrap()])?;
+    println!("Added secret {} for {}", secret.name.bright_white().bold(), secret.scope.to_string().italic());
+    Ok(())
+}
+
+fn edit(context: Context, args: SecretsEditArgs) -> crate::Result<()> {
+    let root = context.project_root().unwrap();
+    let config = context.config().unwrap();
+    let path = resolve_secret(&context, &config, &args.scope, &args.name)?.path();
+    if !root.join(&path).exists() {
+        return Err(context.error(
+            ErrorKind::ValueValidation,
+            format!("{} doesn't exist, use `nico secrets add` to create it.", path.to_str().unwrap()),
+        ));
+    }
+
+    let _sops_version = ensure_sops()?;
+    sops(&root, [path.to_str().unwrap()])?;
+    context.repository()?.add_files([path.to_str().unwrap()])?;
+    Ok(())
+}
+
+fn set(context: Context, args: SecretsSetArgs) -> crate::Result<()> {
+    let mut config = context.config().unwrap();
+    let path = resolve_secret(&context, &config, &args.scope, &args.name)?.path();
+    validate_options(&context, &args.owner, &args.group, &args.mode, &args.restart_units)?;
+    let Some(secret) = config.secrets.files.iter_mut().find(|s| s.path() == path) else {
+        return Err(context.error(
+            ErrorKind::ValueValidation,
+            format!("No secret is recorded for {}.", path.to_str().unwrap()),
+        ));
+    };
+
+    if let Some(owner) = args.owner {
+        secret.owner = Some(owner);
+    }
+    if let Some(group) = args.group {
+        secret.group = Some(group);
+    }
+    if let Some(mode) = args.mode {
+        secret.mode = Some(mode);
+    }
+    if args.clear_restart_units {
+        secret.restart_units.clear();
+    } else if !args.restart_units.is_empty() {
+        secret.restart_units = args.restart_units;
+    }
+
+    let (name, scope) = (secret.name.clone(), secret.scope.to_string());
+    save_project(context, &config)?;
+    println!("Updated secret {} for {}", name.bright_white().bold(), scope.italic());
+    Ok(())
+}
+
+fn get(context: Context, args: SecretsGetArgs) -> crate::Result<()> {
+    let root = context.project_root().unwrap();
+    let config = context.config().unwrap();
+    let path = resolve_secret(&context, &config, &args.scope, &args.name)?.path();
+    if !root.join(&path).exists() {
+        return Err(context.error(
+            ErrorKind::ValueValidation,
+            format!("{} doesn't exist.", path.to_str().unwrap()),
+        ));
+    }
+
+    let _sops_version = ensure_sops()?;
+    let key = args.key.unwrap_or(args.name);
+    // sops' extract syntax is a python-style index, which a JSON string literal satisfies
+    let extract = format!("[{}]", serde_json::to_string(&key)?);
+    sops(&root, ["--decrypt", "--extract", &extract, path.to_str().unwrap()])
+}
+
+fn admin_add(context: Context, args: SecretsAdminAddArgs) -> crate::Result<()> {
+    let mut config = context.config().unwrap();
+    if !valid_recipient(&args.recipient) {
+        return Err(context.error(
+            ErrorKind::ValueValidation,
+            format!("{} is not a valid age recipient.", args.recipient),
+        ));
+    }
+
+    if config.secrets.admins.contains_key(&args.name) {
+        return Err(context.error(
+            ErrorKind::ValueValidation,
+            format!("An admin named {} already exists.", args.name),
+        ));
+    }
+
+    config.secrets.admins.insert(args.name.clone(), args.recipient);
+    save_project(context, &config)?;
+    println!("Added admin {}", args.name.bright_white().bold());
+    Ok(())
+}
+
+fn admin_remove(context: Context, args: SecretsAdminRemoveArgs) -> crate::Result<()> {
+    let mut config = context.config().unwrap();
+    if config.secrets.admins.remove(&args.name).is_none() {
+        return Err(context.error(
+            ErrorKind::ValueValidation,
+            format!("No admin named {} exists.", args.name),
+        ));
+    }
+
+    save_project(context, &config)?;
+    println!("Removed admin {}", args.name.bright_white().bold());
+    Ok(())
+}
+
+fn admin_list(context: Context, _: SecretsAdminListArgs) -> crate::Result<()> {
+    let config = context.config().unwrap();
+    if config.secrets.admins.is_empty() {
+        println!("{}", "No admins configured.".italic());
+    }
+
+    for (name, recipient) in config.secrets.admins {
+        println!("  - {}: {}", name.bright_white().bold(), recipient.italic());
+    }
+    Ok(())
+}
+
+fn rekey(context: Context, args: SecretsRekeyArgs) -> crate::Result<()> {
+    let root = context.project_root().unwrap();
+    let config = context.config().unwrap();
+
+    let mut changed = vec![];
+    for secret in config.secrets.files.iter() {
+        let path = secret.path();
+        if !root.join(&path).exists() {
+            warn!("{path:?} is declared in the config but doesn't exist, skipping.");
+            continue;
+        }
+
+        let Some(metadata) = SopsMetadata::read(root.join(&path))? else {
+            warn!("{path:?} isn't sops-encrypted, skipping.");
+            continue;
+        };
+
+        let (current, expected) = (metadata.recipients(), config.recipients(&secret.scope));
+        if expected.is_empty() {
+            warn!("{path:?} would have no recipients, skipping.");
+        } else if current != expected {
+            let summary = format!(
+                "+{} -{} recipients",
+                expected.difference(&current).count(),
+                current.difference(&expected).count()
+            );
+            println!("{} {} ({summary})", "~".yellow().bold(), path.to_str().unwrap());
+            changed.push((path, summary));
+        }
+    }
+
+    if changed.is_empty() {
+        println!("{}", "All secrets are encrypted for the expected recipients.".italic());
+        return Ok(());
+    }
+    if args.dry_run {
+        return Ok(());
+    }
+
+    let _sops_version = ensure_sops()?;
+    // Make sure sops sees the current creation rules before re-encrypting
+    save_project(context.clone(), &config)?;
+    for (path, _) in changed.iter() {
+        sops(&root, ["updatekeys", "--yes", path.to_str().unwrap()])?;
+    }
+
+    let repo = context.repository()?;
+    let mut committed: Vec<&str> = changed.iter().map(|(p, _)| p.to_str().unwrap()).collect();
+    repo.add_files(committed.iter())?;
+    committed.push(".sops.yaml");
+    let details: Vec<String> = changed
+        .iter()
+        .map(|(path, summary)| format!("- {}: {summary}", path.to_str().unwrap()))
+        .collect();
+    // Only the rekeyed files & the rules they were encrypted with are committed, anything else stays staged
+    repo.commit_paths(
+        format!("Rekey {} secret(s)\n\n{}", changed.len(), details.join("\n")),
+        committed,
+    )?;
+
+    println!("Rekeyed {} secret(s)", changed.len().to_string().bright_white().bold());
+    Ok(())
+}
+
+fn audit_report(context: Context, args: SecretsAuditArgs) -> crate::Result<()> {
+    let root = context.project_root().unwrap();
+    let config = context.config().unwrap();
+    let entries = audit(&config, &root)?;
+
+    if args.json {
+        println!("{}", serde_json::to_string_pretty(&entries)?);
+        return Ok(());
+    }
+
+    if entries.is_empty() {
+        println!("{}", "No problems found.".italic());
+        return Ok(());
+    }
+
+    for entry in entries.iter() {
+        let scope = entry.scope.as_ref().map(|s| format!(" ({s})")).unwrap_or_default();
+        println!("{}{}", entry.path.to_str().unwrap().bright_white().bold(), scope.dimmed());
+        for issue in entry.issues.iter() {
+            let message = match issue {
+                AuditIssue::MissingFile => "declared in the config, but the file doesn't exist".red(),
+                AuditIssue::NotEncrypted => "not encrypted with sops".red(),
+                AuditIssue::MissingRecipient { recipient, owner } => {
+                    format!("{owner} can't decrypt it ({recipient})").red()
+                }
+                AuditIssue::ExtraRecipient { recipient, owner } => format!(
+                    "still encrypted for {} ({recipient})",
+                    owner.as_deref().unwrap_or("an unknown recipient")
+                )
+                .yellow(),
+                AuditIssue::NoHostKey { host } => format!("host {host} has no age key").yellow(),
+                AuditIssue::NoHosts => "no host uses it".yellow(),
+                AuditIssue::Untracked => "not recorded in the config".yellow(),
+            };
+            println!("  - {message}");
+        }
+    }
+
+    println!(
+        "\n{} file(s) with problems, run `nico secrets rekey` to fix recipient mismatches.",
+        entries.len().to_string().bright_white().bold()
+    );
+    Ok(())
+}
+
+pub struct SecretsDispatcher;
+impl Dispatcher for SecretsDispatcher {
+    type Args = SecretsArgs;
+    fn dispatch(context: Context, args: Self::Args) -> crate::Result<()> {
+        match args.operation {
+            SecretsOperations::Add(args) => add(context, args),
+            SecretsOperations::Edit(args) => edit(context, args),
+            SecretsOperations::Get(args) => get(context, args),
+            SecretsOperations::Set(args) => set(context, args),
+            SecretsOperations::Admin(SecretsAdminArgs { operation }) => match operation {
+                SecretsAdminOperations::Add(args) => admin_add(context, args),
+                SecretsAdminOperations::Remove(args) => admin_remove(context, args),
+                SecretsAdminOperations::List(args) => admin_list(context, args),
+            },
+            SecretsOperations::Rekey(args) => rekey(context, args),
+            SecretsOperations::Audit(args) => audit_report(context, args),
+        }
+    }
+}
@@ -1,6 +1,6 @@
 use colored::Colorize;
 
-use crate::{cli::StatusArgs, config::GitRemote, dispatch::Dispatcher};
+use crate::{cli::StatusArgs, config::{GitRemote, Host}, dispatch::Dispatcher};
 
 pub struct StatusDispatcher;
 impl Dispatcher for StatusDispatcher {
@@ -10,11 +10,15 @@ impl Dispatcher for StatusDispatcher {
 
         let config = context.config().unwrap();
         println!("{}\t\tnixpkgs/nixos-{}", "Nix Branch:".bright_white().bold(), config.init.nix.clone());
-        println!("{}\t{}", "Target Architecture:".bright_white().bold(), config.init.system.clone());
+        println!("{}\t{}", "Default Architecture:".bright_white().bold(), config.init.system.clone());
         println!("{}", "Remotes:".bright_white().bold());
         for (name, GitRemote {url, ..}) in config.resources.remotes.clone() {
             println!("  - {name}: {}", url.italic());
         }
+        println!("{}", "Hosts:".bright_white().bold());
+        for (name, Host {system, ..}) in config.hosts.clone() {
+            println!("  - {name}: {}", system.italic());
+        }
         Ok(())
     }
 }
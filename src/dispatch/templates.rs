@@ -0,0 +1,71 @@
+use std::fs;
+
+use clap::error::ErrorKind;
+use colored::Colorize;
+use log::*;
+
+use crate::{
+    cli::{TemplatesArgs, TemplatesEjectArgs, TemplatesListArgs, TemplatesOperations},
+    context::{Context, TEMPLATE_OVERRIDES, builtin_templates, template_overrides},
+    dispatch::Dispatcher,
+    repo::RepoExt,
+};
+
+fn list(context: Context, _: TemplatesListArgs) -> crate::Result<()> {
+    let root = context.project_root().unwrap();
+    let builtin = builtin_templates();
+    let overrides = template_overrides(&root)?;
+
+    for (key, _) in builtin.iter() {
+        match overrides.iter().find(|(k, _)| k == key) {
+            Some((_, path)) => println!(
+                "  - {}: {}",
+                key.bright_white().bold(),
+                format!("overridden by {}", path.strip_prefix(&root).unwrap().to_str().unwrap()).yellow()
+            ),
+            None => println!("  - {}: {}", key.bright_white().bold(), "built-in".italic()),
+        }
+    }
+
+    for (key, _) in overrides.iter().filter(|(k, _)| !builtin.iter().any(|(b, _)| b == k)) {
+        println!("  - {}: {}", key.bright_white().bold(), "custom".green());
+    }
+    Ok(())
+}
+
+fn eject(context: Context, args: TemplatesEjectArgs) -> crate::Result<()> {
+    let root = context.project_root().unwrap();
+    let Some((_, content)) = builtin_templates().into_iter().find(|(k, _)| k == &args.key) else {
+        return Err(context.error(
+            ErrorKind::ValueValidation,
+            format!("No built-in template named {} exists (see `nico templates list`).", args.key),
+        ));
+    };
+
+    let relative = format!("{TEMPLATE_OVERRIDES}/{}.template", args.key);
+    let target = root.join(&relative);
+    if target.exists() && !context.force {
+        return Err(context.error(
+            ErrorKind::ValueValidation,
+            format!("{relative} already exists, re-run with --force to overwrite it."),
+        ));
+    }
+
+    debug!("Writing {target:?}");
+    fs::create_dir_all(target.parent().unwrap())?;
+    fs::write(&target, content)?;
+    context.repository()?.add_files([relative.as_str()])?;
+    println!("Ejected {} to {}", args.key.bright_white().bold(), relative.italic());
+    Ok(())
+}
+
+pub struct TemplatesDispatcher;
+impl Dispatcher for TemplatesDispatcher {
+    type Args = TemplatesArgs;
+    fn dispatch(context: Context, args: Self::Args) -> crate::Result<()> {
+        match args.operation {
+            TemplatesOperations::List(args) => list(context, args),
+            TemplatesOperations::Eject(args) => eject(context, args),
+        }
+    }
+}
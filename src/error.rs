@@ -17,6 +17,18 @@ pub enum Error {
     #[error("JSON error: {0}")]
     Json(Arc<serde_json::Error>),
 
+    #[error("YAML error: {0}")]
+    Yaml(Arc<serde_norway::Error>),
+
+    #[error("TOML parsing error: {0}")]
+    TomlParsing(Arc<toml::de::Error>),
+
+    #[error("TOML serialization error: {0}")]
+    TomlSerializing(Arc<toml::ser::Error>),
+
+    #[error("Failed to parse template: {0}")]
+    TemplateParsing(Arc<handlebars::TemplateError>),
+
     #[error("Failed to render template: {0}")]
     TemplateRendering(Arc<handlebars::RenderError>),
 
@@ -26,6 +38,24 @@ pub enum Error {
     #[error("Git operation error: {0}")]
     Git(Arc<git2::Error>),
 
+    #[error("External command `{0}` failed with code {1}")]
+    ExternalCommand(String, i32),
+
+    #[error("The config is version {0}, but this version of nico only supports up to version {1}. Please update nico.")]
+    ConfigTooNew(u32, u32),
+
+    #[error("The config file must contain a map of settings at the top level")]
+    ConfigNotAMap,
+
+    #[error("Managed regions {1:?} of {0:?} were edited by hand or are missing. Move your changes outside of them, or re-run with --force to overwrite them")]
+    ModifiedRegions(std::path::PathBuf, Vec<String>),
+
+    #[error("Generated files are out of date: {0:?}")]
+    StaleFiles(Vec<std::path::PathBuf>),
+
+    #[error("{0} failed for {1:?}")]
+    HostsFailed(String, Vec<String>),
+
     #[error("This command needs to be run in the project's nix devshell (run `nix develop` in the project root)")]
     OutsideShell
 }
@@ -34,6 +64,10 @@ impl Error {
     pub fn dependency(which: impl Into<String>) -> Self {
         Self::MissingRuntimeDependency(which.into())
     }
+
+    pub fn external(command: impl Into<String>, status: std::process::ExitStatus) -> Self {
+        Self::ExternalCommand(command.into(), status.code().unwrap_or(-1))
+    }
 }
 
 macro_rules! from {
@@ -47,9 +81,13 @@ macro_rules! from {
 }
 
 from!(serde_json::Error, Json);
+from!(serde_norway::Error, Yaml);
+from!(toml::de::Error, TomlParsing);
+from!(toml::ser::Error, TomlSerializing);
 from!(clap::Error, Parsing);
 from!(anyhow::Error, Unknown);
 from!(std::io::Error, Io);
+from!(handlebars::TemplateError, TemplateParsing);
 from!(handlebars::RenderError, TemplateRendering);
 from!(git2::Error, Git);
 
@@ -6,7 +6,10 @@ use std::process::Command;
 pub(crate) use error::{Error, Result};
 pub(crate) mod config;
 pub(crate) mod dispatch;
+pub(crate) mod nix;
+pub(crate) mod regions;
 pub(crate) mod repo;
+pub(crate) mod secrets;
 
 use context::Context;
 use log::{debug, info};
@@ -35,12 +38,29 @@ fn ensure_dependency(command: impl AsRef<str>, args: impl IntoIterator<Item = im
     }
 }
 
+/// Finds a dependency on `PATH` without running it, for commands that have no side-effect free way to print a version.
+fn find_dependency(command: impl AsRef<str>) -> Result<std::path::PathBuf> {
+    let command = command.as_ref();
+    let found = std::env::var_os("PATH")
+        .iter()
+        .flat_map(std::env::split_paths)
+        .map(|directory| directory.join(command))
+        .find(|path| path.is_file());
+    match found {
+        Some(path) => {
+            info!("Found host dependency {command} at {path:?}");
+            Ok(path)
+        }
+        None => Err(Error::dependency(command)),
+    }
+}
+
 fn main() -> Result<()> {
     let context = Context::new()?;
     
     if let Some(ctx) = context {
         env_logger::Builder::new()
-            .filter_level(ctx.verbosity.clone().into())
+            .filter_level(ctx.verbosity.into())
             .init();
 
         let _nix_version = ensure_dependency("nix", ["--version"])?;
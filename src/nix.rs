@@ -0,0 +1,151 @@
+//! Serializing values into nix source, & the handlebars helpers that expose it to templates.
+
+use handlebars::{
+    Context, Handlebars, Helper, HelperResult, Output, RenderContext, RenderErrorReason,
+};
+use serde_json::Value;
+
+use crate::config::valid_ident;
+
+/// Renders a string as a double-quoted nix string, escaping quotes, backslashes & `${` interpolations.
+pub fn string(text: impl AsRef<str>) -> String {
+    let mut escaped = String::from("\"");
+    let mut chars = text.as_ref().chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '"' => escaped.push_str("\\\""),
+            '\\' => escaped.push_str("\\\\"),
+            '\n' => escaped.push_str("\\n"),
+            '\r' => escaped.push_str("\\r"),
+            '\t' => escaped.push_str("\\t"),
+            '$' if chars.peek() == Some(&'{') => escaped.push_str("\\$"),
+            c => escaped.push(c),
+        }
+    }
+    escaped.push('"');
+    escaped
+}
+
+/// Renders an attribute name, quoting it if it isn't a plain identifier.
+pub fn ident(name: impl AsRef<str>) -> String {
+    let name = name.as_ref();
+    if valid_ident(name) {
+        name.to_string()
+    } else {
+        string(name)
+    }
+}
+
+/// Renders any serde value as a nix expression. Nested lines are indented relative to `indent` spaces.
+pub fn value(value: &Value, indent: usize) -> String {
+    let padding = " ".repeat(indent);
+    match value {
+        Value::Null => String::from("null"),
+        Value::Bool(b) => b.to_string(),
+        Value::Number(n) => n.to_string(),
+        Value::String(s) => string(s),
+        Value::Array(items) if items.is_empty() => String::from("[ ]"),
+        Value::Array(items) if items.iter().all(|i| !i.is_array() && !i.is_object()) => format!(
+            "[ {} ]",
+            items.iter().map(|i| self::value(i, indent)).collect::<Vec<_>>().join(" ")
+        ),
+        Value::Array(items) => {
+            let lines: Vec<String> = items
+                .iter()
+                .map(|i| format!("{padding}  {}", self::value(i, indent + 2)))
+                .collect();
+            format!("[\n{}\n{padding}]", lines.join("\n"))
+        }
+        Value::Object(attrs) if attrs.is_empty() => String::from("{ }"),
+        Value::Object(attrs) => {
+            let lines: Vec<String> = attrs
+                .iter()
+                .map(|(k, v)| format!("{padding}  {} = {};", ident(k), self::value(v, indent + 2)))
+                .collect();
+            format!("{{\n{}\n{padding}}}", lines.join("\n"))
+        }
+    }
+}
+
+/// Builds a helper that renders its first parameter with `render`, if `accepts` it.
+/// An optional `indent=N` hash parameter sets the indentation of nested lines.
+fn helper(
+    name: &'static str,
+    accepts: fn(&Value) -> bool,
+    render: fn(&Value, usize) -> String,
+) -> impl Fn(&Helper, &Handlebars, &Context, &mut RenderContext, &mut dyn Output) -> HelperResult + Send + Sync {
+    move |h, _, _, _, out| {
+        let param = h
+            .param(0)
+            .ok_or(RenderErrorReason::ParamNotFoundForIndex(name, 0))?
+            .value();
+        if !accepts(param) {
+            return Err(RenderErrorReason::InvalidParamType(name).into());
+        }
+
+        let indent = h.hash_get("indent").and_then(|i| i.value().as_u64()).unwrap_or(0);
+        out.write(&render(param, indent as usize))?;
+        Ok(())
+    }
+}
+
+/// Registers the `nix`, `nix-string`, `nix-ident`, `nix-list` & `nix-attrset` helpers.
+pub fn register_helpers(templater: &mut Handlebars) {
+    fn scalar(v: &Value) -> String {
+        match v {
+            Value::String(s) => s.clone(),
+            other => other.to_string(),
+        }
+    }
+
+    templater.register_helper("nix", Box::new(helper("nix", |_| true, value)));
+    templater.register_helper(
+        "nix-string",
+        Box::new(helper("nix-string", |v| !v.is_array() && !v.is_object() && !v.is_null(), |v, _| string(scalar(v)))),
+    );
+    templater.register_helper(
+        "nix-ident",
+        Box::new(helper("nix-ident", |v| v.is_string(), |v, _| ident(scalar(v)))),
+    );
+    templater.register_helper("nix-list", Box::new(helper("nix-list", Value::is_array, value)));
+    templater.register_helper("nix-attrset", Box::new(helper("nix-attrset", Value::is_object, value)));
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    #[test]
+    fn escapes_strings() {
+        assert_eq!(string(r#"say "hi""#), r#""say \"hi\"""#);
+        assert_eq!(string(r"C:\dir"), r#""C:\\dir""#);
+        assert_eq!(string("${builtins.abort 1}"), r#""\${builtins.abort 1}""#);
+        assert_eq!(string("$HOME costs $5"), r#""$HOME costs $5""#);
+        assert_eq!(string("a\nb\tc\r"), r#""a\nb\tc\r""#);
+        assert_eq!(string("$${x}"), r#""$\${x}""#);
+    }
+
+    #[test]
+    fn quotes_idents_only_when_needed() {
+        assert_eq!(ident("nixpkgs"), "nixpkgs");
+        assert_eq!(ident("sops-nix"), "sops-nix");
+        assert_eq!(ident("let"), r#""let""#);
+        assert_eq!(ident("with"), r#""with""#);
+        assert_eq!(ident("1password"), r#""1password""#);
+        assert_eq!(ident("has space"), r#""has space""#);
+        assert_eq!(ident("${x}"), r#""\${x}""#);
+    }
+
+    #[test]
+    fn renders_values() {
+        assert_eq!(value(&json!(null), 0), "null");
+        assert_eq!(value(&json!(["a", 1, true]), 0), r#"[ "a" 1 true ]"#);
+        assert_eq!(value(&json!({}), 0), "{ }");
+        assert_eq!(
+            value(&json!({"in": "${x}", "ok": [{"a": 1}]}), 2),
+            "{\n    \"in\" = \"\\${x}\";\n    ok = [\n      {\n        a = 1;\n      }\n    ];\n  }"
+        );
+    }
+}
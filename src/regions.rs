@@ -0,0 +1,410 @@
+//! Managed regions of generated files.
+//!
+//! Templates wrap everything nico generates in `# nico:begin <region>` / `# nico:end` markers. When a file is
+//! re-rendered, only those regions are replaced, so hand edits outside of them survive. Each begin marker records
+//! a hash of the region's content, which is how hand edits *inside* a region are detected.
+//!
+//! Regions a template gains over time are inserted into existing files directly after the region that precedes them
+//! in the template (or before the one that follows them, if they come first), so templates should add new regions
+//! next to existing ones.
+
+use std::collections::BTreeMap;
+
+const BEGIN: &str = "# nico:begin ";
+const END: &str = "# nico:end";
+
+#[derive(Clone, Debug)]
+struct Region {
+    name: String,
+    hash: Option<String>,
+    /// Index of the begin marker line
+    begin: usize,
+    /// Index of the end marker line
+    end: usize,
+}
+
+/// 64-bit FNV-1a, which is stable across builds (unlike std's hashers).
+fn fnv1a(text: &str) -> String {
+    let hash = text.bytes().fold(0xcbf29ce484222325u64, |hash, byte| {
+        (hash ^ byte as u64).wrapping_mul(0x100000001b3)
+    });
+    format!("{hash:016x}")
+}
+
+fn parse(lines: &[&str]) -> Vec<Region> {
+    let mut regions = vec![];
+    let mut open: Option<(String, Option<String>, usize)> = None;
+    for (index, line) in lines.iter().enumerate() {
+        let trimmed = line.trim();
+        if let Some(marker) = trimmed.strip_prefix(BEGIN) {
+            let mut parts = marker.split_whitespace();
+            if let Some(name) = parts.next() {
+                open = Some((name.to_string(), parts.next().map(String::from), index));
+            }
+        } else if trimmed == END
+            && let Some((name, hash, begin)) = open.take()
+        {
+            regions.push(Region {
+                name,
+                hash,
+                begin,
+                end: index,
+            });
+        }
+    }
+    regions
+}
+
+fn body(lines: &[&str], region: &Region) -> String {
+    lines[region.begin + 1..region.end].join("\n")
+}
+
+/// Whether the text contains any managed regions.
+pub fn has_regions(text: &str) -> bool {
+    !parse(&text.lines().collect::<Vec<_>>()).is_empty()
+}
+
+/// Records the hash of every region's content in its begin marker.
+pub fn stamp(rendered: &str) -> String {
+    let mut lines: Vec<String> = rendered.lines().map(String::from).collect();
+    let borrowed: Vec<&str> = rendered.lines().collect();
+    for region in parse(&borrowed) {
+        let indent: String = borrowed[region.begin]
+            .chars()
+            .take_while(|c| c.is_whitespace())
+            .collect();
+        lines[region.begin] = format!("{indent}{BEGIN}{} {}", region.name, fnv1a(&body(&borrowed, &region)));
+    }
+
+    let mut stamped = lines.join("\n");
+    if rendered.ends_with('\n') {
+        stamped.push('\n');
+    }
+    stamped
+}
+
+/// Unmanaged text that an older template generated, which a newer template manages as a region.
+pub struct Legacy {
+    /// Region that takes the text over
+    pub region: &'static str,
+    /// The text as the older template generated it. The content of regions inside it doesn't matter, since they're
+    /// dissolved into the new region.
+    pub text: &'static str,
+}
+
+/// The trimmed, non-blank lines of some text, minus any regions in it.
+fn unmanaged(lines: &[&str]) -> Vec<String> {
+    let regions = parse(lines);
+    lines
+        .iter()
+        .enumerate()
+        .filter(|(index, line)| {
+            !line.trim().is_empty() && !regions.iter().any(|r| (r.begin..=r.end).contains(index))
+        })
+        .map(|(_, line)| line.trim().to_string())
+        .collect()
+}
+
+/// Wraps the text described by `legacy` in markers for its new region, so that [`merge`] replaces it like any other
+/// region. The region is only stamped (ie. treated as unedited) if the text & the regions inside it are still exactly
+/// what the older template generated; otherwise it's reported as edited. Files that already have the region, don't
+/// contain the legacy text, or don't have any regions at all (so there'd be nothing to place the others next to),
+/// are returned unchanged.
+pub fn adopt(existing: &str, legacy: &Legacy) -> String {
+    let lines: Vec<&str> = existing.lines().collect();
+    let regions = parse(&lines);
+    let expected = unmanaged(&legacy.text.lines().collect::<Vec<_>>());
+    let (Some(first), Some(last)) = (expected.first(), expected.last()) else {
+        return existing.to_string();
+    };
+    if regions.is_empty() || regions.iter().any(|r| r.name == legacy.region) {
+        return existing.to_string();
+    }
+
+    let managed = |index: usize| regions.iter().any(|r| (r.begin..=r.end).contains(&index));
+    let Some(begin) = (0..lines.len()).find(|&i| !managed(i) && lines[i].trim() == first) else {
+        return existing.to_string();
+    };
+    let indent: String = lines[begin].chars().take_while(|c| c.is_whitespace()).collect();
+    let Some(end) = (begin + 1..lines.len()).find(|&i| {
+        !managed(i) && lines[i].trim() == last && lines[i].strip_prefix(indent.as_str()) == Some(last.as_str())
+    }) else {
+        return existing.to_string();
+    };
+
+    let span = &lines[begin..=end];
+    let inner = parse(span);
+    let unedited = unmanaged(span) == expected
+        && inner
+            .iter()
+            .all(|r| r.hash.as_deref() == Some(fnv1a(&body(span, r)).as_str()));
+    let markers: Vec<usize> = inner.iter().flat_map(|r| [r.begin, r.end]).collect();
+    let adopted: Vec<&str> = span
+        .iter()
+        .enumerate()
+        .filter(|(index, _)| !markers.contains(index))
+        .map(|(_, line)| *line)
+        .collect();
+
+    let begin_marker = if unedited {
+        format!("{indent}{BEGIN}{} {}", legacy.region, fnv1a(&adopted.join("\n")))
+    } else {
+        format!("{indent}{BEGIN}{}", legacy.region)
+    };
+    let end_marker = format!("{indent}{END}");
+
+    let mut output: Vec<&str> = lines[..begin].to_vec();
+    output.push(&begin_marker);
+    output.extend(adopted);
+    output.push(&end_marker);
+    output.extend(&lines[end + 1..]);
+
+    let mut output = output.join("\n");
+    if existing.ends_with('\n') {
+        output.push('\n');
+    }
+    output
+}
+
+/// Replaces the managed regions of `existing` with the ones in `rendered` (which should already be stamped).
+///
+/// Returns the names of the regions that couldn't be replaced safely, either because they were edited by hand or
+/// because they're missing from `existing` & there's no shared region to insert them next to. With `force`, edited
+/// regions are overwritten anyway, and if no regions are shared at all the whole file is replaced.
+pub fn merge(existing: &str, rendered: &str, force: bool) -> Result<String, Vec<String>> {
+    let old_lines: Vec<&str> = existing.lines().collect();
+    let new_lines: Vec<&str> = rendered.lines().collect();
+    let old_regions = parse(&old_lines);
+    let new_regions = parse(&new_lines);
+    let position = |name: &str| old_regions.iter().position(|r| r.name == name);
+
+    // Regions missing from `existing`, keyed by the index of the existing region they're inserted after (or before)
+    let mut after: BTreeMap<usize, Vec<&Region>> = BTreeMap::new();
+    let mut before: BTreeMap<usize, Vec<&Region>> = BTreeMap::new();
+    let mut unplaced = vec![];
+    for (index, region) in new_regions.iter().enumerate() {
+        if position(&region.name).is_some() {
+            continue;
+        }
+        if let Some(anchor) = new_regions[..index].iter().rev().find_map(|r| position(&r.name)) {
+            after.entry(anchor).or_default().push(region);
+        } else if let Some(anchor) = new_regions[index + 1..].iter().find_map(|r| position(&r.name)) {
+            before.entry(anchor).or_default().push(region);
+        } else {
+            unplaced.push(region.name.clone());
+        }
+    }
+    if !unplaced.is_empty() {
+        return if force { Ok(rendered.to_string()) } else { Err(unplaced) };
+    }
+
+    let mut merged: Vec<&str> = vec![];
+    let mut modified = vec![];
+    let mut cursor = 0;
+    for (index, region) in old_regions.iter().enumerate() {
+        merged.extend(&old_lines[cursor..region.begin]);
+        cursor = region.end + 1;
+        for inserted in before.get(&index).into_iter().flatten() {
+            merged.extend(&new_lines[inserted.begin..=inserted.end]);
+        }
+
+        match new_regions.iter().find(|r| r.name == region.name) {
+            // Regions that are no longer rendered are left as-is
+            None => merged.extend(&old_lines[region.begin..=region.end]),
+            Some(replacement) => {
+                let edited = region.hash.as_deref() != Some(fnv1a(&body(&old_lines, region)).as_str());
+                if edited && !force {
+                    modified.push(region.name.clone());
+                    merged.extend(&old_lines[region.begin..=region.end]);
+                } else {
+                    merged.extend(&new_lines[replacement.begin..=replacement.end]);
+                }
+            }
+        }
+
+        for inserted in after.get(&index).into_iter().flatten() {
+            merged.extend(&new_lines[inserted.begin..=inserted.end]);
+        }
+    }
+    merged.extend(&old_lines[cursor..]);
+
+    if !modified.is_empty() {
+        return Err(modified);
+    }
+
+    let mut output = merged.join("\n");
+    if rendered.ends_with('\n') {
+        output.push('\n');
+    }
+    Ok(output)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TEMPLATE: &str = "{\n  # nico:begin a\n  a = 1;\n  # nico:end\n  # nico:begin b\n  b = 2;\n  # nico:end\n}\n";
+
+    #[test]
+    fn replaces_regions_and_keeps_edits_outside_them() {
+        let existing = stamp(TEMPLATE).replace("{\n", "{\n  custom = true;\n");
+        let rendered = stamp(&TEMPLATE.replace("a = 1", "a = 3"));
+        let merged = merge(&existing, &rendered, false).unwrap();
+        assert!(merged.contains("custom = true;"));
+        assert!(merged.contains("a = 3;"));
+        assert_eq!(merged, rendered.replace("{\n", "{\n  custom = true;\n"));
+    }
+
+    #[test]
+    fn reports_edited_regions() {
+        let existing = stamp(TEMPLATE).replace("b = 2", "b = 42");
+        let rendered = stamp(&TEMPLATE.replace("a = 1", "a = 3"));
+        assert_eq!(merge(&existing, &rendered, false), Err(vec![String::from("b")]));
+
+        let forced = merge(&existing, &rendered, true).unwrap();
+        assert!(forced.contains("b = 2;") && forced.contains("a = 3;"));
+    }
+
+    #[test]
+    fn inserts_missing_regions_next_to_their_neighbours() {
+        let existing = stamp(TEMPLATE).replace("}\n", "  custom = true;\n}\n");
+        let rendered = stamp(&TEMPLATE.replace("  # nico:begin b", "  # nico:begin new\n  new = 0;\n  # nico:end\n  # nico:begin b"));
+        let merged = merge(&existing, &rendered, false).unwrap();
+        let lines: Vec<&str> = merged.lines().map(str::trim).collect();
+        let position = |line: &str| lines.iter().position(|l| *l == line).unwrap();
+        assert!(position("a = 1;") < position("new = 0;") && position("new = 0;") < position("b = 2;"));
+        assert!(merged.contains("custom = true;"));
+
+        // A new first region goes before the region that follows it
+        let rendered = stamp(&TEMPLATE.replace("  # nico:begin a", "  # nico:begin first\n  first = 0;\n  # nico:end\n  # nico:begin a"));
+        let merged = merge(&existing, &rendered, false).unwrap();
+        assert!(merged.find("first = 0;").unwrap() < merged.find("a = 1;").unwrap());
+    }
+
+    #[test]
+    fn reports_missing_regions_without_an_anchor() {
+        let existing = stamp("# nico:begin old\nold = 1;\n# nico:end\n");
+        let rendered = stamp("# nico:begin new\nnew = 1;\n# nico:end\n");
+        assert_eq!(merge(&existing, &rendered, false), Err(vec![String::from("new")]));
+        assert_eq!(merge(&existing, &rendered, true), Ok(rendered));
+    }
+
+    #[test]
+    fn only_replaces_files_without_regions_when_forced() {
+        let existing = "{\n  a = 1;\n  custom = true;\n}\n";
+        let rendered = stamp(TEMPLATE);
+        assert_eq!(merge(existing, &rendered, false), Err(vec![String::from("a"), String::from("b")]));
+        assert_eq!(merge(existing, &rendered, true), Ok(rendered));
+    }
+
+    #[test]
+    fn keeps_regions_that_are_no_longer_rendered() {
+        let existing = stamp(TEMPLATE);
+        let rendered = stamp("{\n  # nico:begin a\n  a = 1;\n  # nico:end\n}\n");
+        let merged = merge(&existing, &rendered, false).unwrap();
+        assert_eq!(merged, existing);
+    }
+
+    #[test]
+    fn follows_the_rendered_trailing_newline() {
+        let existing = stamp(TEMPLATE);
+        assert!(merge(existing.trim_end(), &existing, false).unwrap().ends_with("}\n"));
+        assert!(!merge(&existing, existing.trim_end(), false).unwrap().ends_with('\n'));
+    }
+
+    const LEGACY: Legacy = Legacy {
+        region: "shell",
+        text: "shell = mkShell {\n  # nico:begin packages\n  # nico:end\n};\n",
+    };
+
+    #[test]
+    fn adopts_unedited_legacy_text() {
+        let existing = stamp("{\n  shell = mkShell {\n    # nico:begin packages\n    jq\n    # nico:end\n  };\n}\n");
+        let adopted = adopt(&existing, &LEGACY);
+        assert!(adopted.starts_with("{\n  # nico:begin shell "));
+        assert!(!adopted.contains("nico:begin packages"));
+
+        let rendered = stamp("{\n  # nico:begin shell\n  shell = forAll mkShell;\n  # nico:end\n}\n");
+        assert_eq!(merge(&adopted, &rendered, false), Ok(rendered));
+    }
+
+    #[test]
+    fn reports_edited_legacy_text() {
+        let existing = stamp("{\n  shell = mkShell {\n    # nico:begin packages\n    jq\n    # nico:end\n    custom = 1;\n  };\n}\n");
+        let rendered = stamp("{\n  # nico:begin shell\n  shell = forAll mkShell;\n  # nico:end\n}\n");
+        assert_eq!(merge(&adopt(&existing, &LEGACY), &rendered, false), Err(vec![String::from("shell")]));
+
+        // Hand edits to regions inside the legacy text count too
+        let existing = stamp("{\n  shell = mkShell {\n    # nico:begin packages\n    jq\n    # nico:end\n  };\n}\n").replace("jq", "yq");
+        assert_eq!(merge(&adopt(&existing, &LEGACY), &rendered, false), Err(vec![String::from("shell")]));
+    }
+
+    /// `flake.nix` as nico generated it before it had any managed regions.
+    const BASELINE_FLAKE: &str = r#"{
+  description = "My flake";
+  inputs = {
+    nixpkgs.url = "nixpkgs/nixos-unstable";
+    sops-nix = {
+      url = "github:Mic92/sops-nix";
+      inputs.nixpkgs.follows = "nixpkgs";
+    };
+    comin = {
+      url = "github:nlewo/comin";
+      inputs.nixpkgs.follows = "nixpkgs";
+    };
+  };
+
+  outputs =
+    {
+      self,
+      nixpkgs,
+      sops-nix,
+      comin,
+      ...
+    }@inputs:
+    let
+      system = "x86_64-linux";
+      pkgs = import nixpkgs { inherit system; };
+      nico-override-env = builtins.getEnv "NICO_OVERRIDE_ENV";
+    in
+    {
+      nixosConfigurations = { };
+      devShells.${system}.default = pkgs.mkShell {
+        packages = [
+          pkgs.age
+          pkgs.sops
+          pkgs.jq
+        ];
+
+        shellHook = if nico-override-env == "" then ''
+          flake_store_root="${builtins.toString ./.}"
+          if repo_root="$( \
+            2>/dev/null git rev-parse --show-toplevel \
+          )"; then
+            flake_root="$repo_root"
+          else
+            flake_root="$flake_store_root"
+          fi
+          export NICO_ENV="$flake_root"
+        '' else ''
+          export NICO_ENV="${nico-override-env}"
+        '';
+      };
+    };
+}
+"#;
+
+    #[test]
+    fn leaves_files_without_regions_to_merge() {
+        for legacy in &crate::config::LEGACY_FLAKE {
+            assert_eq!(adopt(BASELINE_FLAKE, legacy), BASELINE_FLAKE);
+        }
+
+        // With nothing to anchor them to, every region is reported instead of being scattered around the file
+        let rendered = stamp("{\n  # nico:begin system\n  system = 1;\n  # nico:end\n  # nico:begin devshells\n  devShells = 1;\n  # nico:end\n}\n");
+        assert_eq!(
+            merge(BASELINE_FLAKE, &rendered, false),
+            Err(vec![String::from("system"), String::from("devshells")])
+        );
+    }
+}
@@ -1,9 +1,42 @@
-use git2::{IndexAddOption, Repository};
+use std::path::{Path, PathBuf};
+
+use git2::{BranchType, Index, IndexAddOption, Repository, Worktree, WorktreeAddOptions, WorktreePruneOptions};
+use log::{debug, warn};
+
+/// A worktree checked out at some revision in a temporary directory, which is pruned (along with its branch) when
+/// dropped.
+pub struct TemporaryWorktree {
+    worktree: Worktree,
+    repository: PathBuf,
+    branch: String,
+}
+
+impl TemporaryWorktree {
+    pub fn path(&self) -> &Path {
+        self.worktree.path()
+    }
+}
+
+impl Drop for TemporaryWorktree {
+    fn drop(&mut self) {
+        debug!("Removing temporary worktree {:?}", self.path());
+        let pruned = self
+            .worktree
+            .prune(Some(WorktreePruneOptions::new().valid(true).locked(true).working_tree(true)));
+        let deleted = Repository::open(&self.repository)
+            .and_then(|r| r.find_branch(&self.branch, BranchType::Local)?.delete());
+        if let Err(e) = pruned.and(deleted) {
+            warn!("Failed to clean up temporary worktree {}: {e}", self.branch);
+        }
+    }
+}
 
 pub trait RepoExt {
     fn create_initial_commit(&self) -> crate::Result<()>;
     fn create_commit(&self, message: impl AsRef<str>) -> crate::Result<()>;
     fn add_files(&self, paths: impl IntoIterator<Item = impl AsRef<str>>) -> crate::Result<()>;
+    fn commit_paths(&self, message: impl AsRef<str>, paths: impl IntoIterator<Item = impl AsRef<str>>) -> crate::Result<()>;
+    fn temporary_worktree(&self, revision: impl AsRef<str>) -> crate::Result<TemporaryWorktree>;
 }
 
 impl RepoExt for Repository {
@@ -40,13 +73,63 @@ impl RepoExt for Repository {
     }
 
     fn add_files(&self, paths: impl IntoIterator<Item = impl AsRef<str>>) -> crate::Result<()> {
+        let paths: Vec<String> = paths.into_iter().map(|v| v.as_ref().to_string()).collect();
         let mut index = self.index()?;
-        index.add_all(
-            paths.into_iter().map(|v| v.as_ref().to_string()),
-            IndexAddOption::DEFAULT,
-            None,
-        )?;
+        index.add_all(paths.iter(), IndexAddOption::DEFAULT, None)?;
+        // Stage deletions as well, so removed files don't linger in the flake's view of the repo
+        index.update_all(paths.iter(), None)?;
         index.write()?;
         Ok(())
     }
+
+    fn commit_paths(&self, message: impl AsRef<str>, paths: impl IntoIterator<Item = impl AsRef<str>>) -> crate::Result<()> {
+        let parent_commit = self.head()?.peel_to_commit()?;
+        let staged = self.index()?;
+
+        // Start from HEAD so that anything else that's staged stays out of the commit (& stays staged)
+        let mut index = Index::new()?;
+        index.read_tree(&parent_commit.tree()?)?;
+        for path in paths {
+            let path = Path::new(path.as_ref());
+            match staged.get_path(path, 0) {
+                Some(entry) => index.add(&entry)?,
+                None => index.remove_path(path)?,
+            }
+        }
+
+        let tree = self.find_tree(index.write_tree_to(self)?)?;
+        let signature = self.signature()?;
+        self.commit(
+            Some("HEAD"),
+            &signature,
+            &signature,
+            message.as_ref(),
+            &tree,
+            &[&parent_commit],
+        )?;
+        Ok(())
+    }
+
+    fn temporary_worktree(&self, revision: impl AsRef<str>) -> crate::Result<TemporaryWorktree> {
+        let commit = self.revparse_single(revision.as_ref())?.peel_to_commit()?;
+        let name = format!("nico-{}-{}", std::process::id(), &commit.id().to_string()[..8]);
+        let path = std::env::temp_dir().join(&name);
+        debug!("Checking out {} into temporary worktree {path:?}", commit.id());
+
+        // Worktrees need a branch to check out, so give this one its own that's deleted along with it
+        let mut branch = self.branch(&name, &commit, true)?;
+        let added = self.worktree(&name, &path, Some(WorktreeAddOptions::new().reference(Some(branch.get()))));
+        let worktree = match added {
+            Ok(worktree) => worktree,
+            Err(e) => {
+                branch.delete()?;
+                return Err(e.into());
+            }
+        };
+        Ok(TemporaryWorktree {
+            worktree,
+            repository: self.path().to_path_buf(),
+            branch: name,
+        })
+    }
 }
@@ -0,0 +1,353 @@
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fs,
+    io::Write,
+    os::unix::fs::OpenOptionsExt,
+    path::{Path, PathBuf},
+    process::{Command, Stdio},
+    str::FromStr,
+};
+
+use age::secrecy::ExposeSecret;
+use base64::Engine;
+use bech32::{ToBase32, Variant};
+use curve25519_dalek::edwards::CompressedEdwardsY;
+use log::*;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    config::{Configuration, SecretScope},
+    ensure_dependency,
+};
+
+#[derive(Deserialize, Clone, Debug)]
+pub struct AgeStanza {
+    pub recipient: String,
+}
+
+/// The parts of a sops file's (unencrypted) `sops` metadata block that nico cares about.
+#[derive(Deserialize, Clone, Debug)]
+pub struct SopsMetadata {
+    #[serde(default)]
+    pub age: Vec<AgeStanza>,
+}
+
+impl SopsMetadata {
+    /// Reads the metadata of a YAML/JSON sops file, or `None` if the file isn't sops-encrypted.
+    pub fn read(path: impl AsRef<Path>) -> crate::Result<Option<Self>> {
+        let raw = fs::read_to_string(path)?;
+        let Ok(serde_norway::Value::Mapping(mut document)) = serde_norway::from_str(&raw) else {
+            return Ok(None);
+        };
+
+        match document.remove("sops") {
+            Some(metadata) => Ok(serde_norway::from_value(metadata).ok()),
+            None => Ok(None),
+        }
+    }
+
+    pub fn recipients(&self) -> BTreeSet<String> {
+        self.age.iter().map(|a| a.recipient.clone()).collect()
+    }
+}
+
+/// Confirms that sops is available, returning its version string.
+pub fn ensure_sops() -> crate::Result<String> {
+    ensure_dependency("sops", ["--version"])
+}
+
+/// Runs sops in the project root (so `.sops.yaml` is picked up), failing if it exits unsuccessfully.
+pub fn sops(root: impl AsRef<Path>, args: impl IntoIterator<Item = impl AsRef<str>>) -> crate::Result<()> {
+    sops_with_input(root, args, None::<&str>)
+}
+
+/// Same as [`sops`], optionally writing `input` to its stdin (eg. for encrypting `/dev/stdin` without a plaintext file).
+pub fn sops_with_input(
+    root: impl AsRef<Path>,
+    args: impl IntoIterator<Item = impl AsRef<str>>,
+    input: Option<impl AsRef<str>>,
+) -> crate::Result<()> {
+    let args: Vec<String> = args.into_iter().map(|a| a.as_ref().to_string()).collect();
+    debug!("Running sops {}", args.join(" "));
+
+    let mut command = Command::new("sops");
+    command.args(&args).current_dir(root);
+    let status = match input {
+        Some(input) => {
+            let mut child = command.stdin(Stdio::piped()).spawn()?;
+            child
+                .stdin
+                .take()
+                .unwrap()
+                .write_all(input.as_ref().as_bytes())?;
+            child.wait()?
+        }
+        None => command.status()?,
+    };
+
+    if status.success() {
+        Ok(())
+    } else {
+        Err(crate::Error::external(format!("sops {}", args.join(" ")), status))
+    }
+}
+
+/// Reads one length-prefixed string from an SSH wire-format buffer, returning it & the remainder.
+fn read_ssh_string(buffer: &[u8]) -> Option<(&[u8], &[u8])> {
+    let length = u32::from_be_bytes(buffer.get(..4)?.try_into().ok()?) as usize;
+    let value = buffer.get(4..4 + length)?;
+    Some((value, &buffer[4 + length..]))
+}
+
+/// Converts an OpenSSH ed25519 public key blob (base64, as it appears in `authorized_keys`) into an age recipient.
+/// The ed25519 point is mapped onto its birationally-equivalent curve25519 point, same as `ssh-to-age`.
+pub fn ssh_ed25519_to_age(blob: impl AsRef<str>) -> Option<String> {
+    let decoded = base64::engine::general_purpose::STANDARD
+        .decode(blob.as_ref())
+        .ok()?;
+    let (key_type, rest) = read_ssh_string(&decoded)?;
+    let (key, _) = read_ssh_string(rest)?;
+    if key_type != b"ssh-ed25519" {
+        return None;
+    }
+
+    let point = CompressedEdwardsY::from_slice(key).ok()?.decompress()?;
+    bech32::encode("age", point.to_montgomery().to_bytes().to_base32(), Variant::Bech32).ok()
+}
+
+/// Finds the first ed25519 key in a public key file or `ssh-keyscan` output and converts it to an age recipient.
+pub fn find_ssh_recipient(text: impl AsRef<str>) -> Option<String> {
+    text.as_ref()
+        .lines()
+        .filter(|line| !line.trim_start().starts_with('#'))
+        .find_map(|line| {
+            let fields: Vec<&str> = line.split_whitespace().collect();
+            let position = fields.iter().position(|f| *f == "ssh-ed25519")?;
+            ssh_ed25519_to_age(fields.get(position + 1)?)
+        })
+}
+
+/// Location sops reads age identities from by default (`$SOPS_AGE_KEY_FILE`, or `sops/age/keys.txt` in the user config dir).
+pub fn sops_key_file() -> Option<PathBuf> {
+    if let Ok(path) = std::env::var("SOPS_AGE_KEY_FILE") {
+        return Some(PathBuf::from(path));
+    }
+
+    let config_dir = match std::env::var("XDG_CONFIG_HOME") {
+        Ok(dir) if !dir.is_empty() => PathBuf::from(dir),
+        _ => {
+            let home = PathBuf::from(std::env::var("HOME").ok()?);
+            if cfg!(target_os = "macos") {
+                home.join("Library/Application Support")
+            } else {
+                home.join(".config")
+            }
+        }
+    };
+    Some(config_dir.join("sops/age/keys.txt"))
+}
+
+/// Returns the recipient of the first identity in the given key file, generating a new identity if the file doesn't exist.
+/// The boolean is `true` if a new identity was generated.
+pub fn ensure_identity(path: impl AsRef<Path>) -> crate::Result<(String, bool)> {
+    let path = path.as_ref();
+    if path.exists() {
+        let identity = fs::read_to_string(path)?
+            .lines()
+            .find_map(|line| age::x25519::Identity::from_str(line.trim()).ok())
+            .ok_or_else(|| anyhow::anyhow!("{path:?} doesn't contain an age identity"))?;
+        return Ok((identity.to_public().to_string(), false));
+    }
+
+    let identity = age::x25519::Identity::generate();
+    let recipient = identity.to_public().to_string();
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+
+    info!("Writing new age identity to {path:?}");
+    let mut file = fs::OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .mode(0o600)
+        .open(path)?;
+    writeln!(file, "# created by nico")?;
+    writeln!(file, "# public key: {recipient}")?;
+    writeln!(file, "{}", identity.to_string().expose_secret())?;
+    Ok((recipient, true))
+}
+
+/// A problem found with a secret file by [`audit`].
+#[derive(Serialize, Clone, Debug)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum AuditIssue {
+    /// The file is declared in the config but doesn't exist
+    MissingFile,
+    /// The file is declared in the config but isn't sops-encrypted
+    NotEncrypted,
+    /// An expected recipient can't decrypt the file
+    MissingRecipient { recipient: String, owner: String },
+    /// The file is encrypted for a recipient that shouldn't be able to decrypt it (eg. a removed host or admin)
+    ExtraRecipient { recipient: String, owner: Option<String> },
+    /// A host that should read the file has no age key, so it can't decrypt anything
+    NoHostKey { host: String },
+    /// No host matches the file's scope, so nothing deploys it
+    NoHosts,
+    /// The file is encrypted but has no secret record in the config
+    Untracked,
+}
+
+#[derive(Serialize, Clone, Debug)]
+pub struct AuditEntry {
+    pub path: PathBuf,
+    pub scope: Option<String>,
+    pub issues: Vec<AuditIssue>,
+}
+
+/// Finds every sops-encrypted YAML/JSON file under `directory`, skipping hidden directories & symlinks.
+fn encrypted_files(root: &Path, directory: &Path, found: &mut BTreeMap<PathBuf, SopsMetadata>) -> crate::Result<()> {
+    for entry in fs::read_dir(root.join(directory))? {
+        let entry = entry?;
+        let (path, kind) = (directory.join(entry.file_name()), entry.file_type()?);
+        if entry.file_name().to_string_lossy().starts_with('.') || kind.is_symlink() {
+            continue;
+        }
+
+        if kind.is_dir() {
+            encrypted_files(root, &path, found)?;
+        } else if matches!(path.extension().and_then(|e| e.to_str()), Some("yaml" | "yml" | "json"))
+            && let Ok(Some(metadata)) = SopsMetadata::read(root.join(&path))
+        {
+            found.insert(path, metadata);
+        }
+    }
+    Ok(())
+}
+
+/// Infers the scope of a file from its location under `secrets/`.
+fn scope_from_path(path: &Path) -> Option<SecretScope> {
+    let mut components = path.strip_prefix("secrets").ok()?.iter().map(|c| c.to_str());
+    match (components.next()??, components.next()??) {
+        ("hosts", host) => Some(SecretScope::Host(host.to_string())),
+        ("groups", group) => Some(SecretScope::Group(group.to_string())),
+        _ => None,
+    }
+}
+
+/// Compares the recipients of every encrypted file under `root` against the ones the config expects, without decrypting anything.
+/// Only files with at least one issue are returned.
+pub fn audit(config: &Configuration, root: impl AsRef<Path>) -> crate::Result<Vec<AuditEntry>> {
+    let root = root.as_ref();
+    let mut found = BTreeMap::new();
+    encrypted_files(root, Path::new(""), &mut found)?;
+
+    let owners: BTreeMap<&String, String> = config
+        .secrets
+        .admins
+        .iter()
+        .map(|(name, recipient)| (recipient, format!("admin {name}")))
+        .chain(
+            config
+                .hosts
+                .values()
+                .filter_map(|h| h.age_key.as_ref().map(|key| (key, format!("host {}", h.name)))),
+        )
+        .collect();
+
+    let mut entries = vec![];
+    for secret in config.secrets.files.iter() {
+        let path = secret.path();
+        let issue = if !root.join(&path).exists() {
+            AuditIssue::MissingFile
+        } else if !found.contains_key(&path) {
+            AuditIssue::NotEncrypted
+        } else {
+            continue;
+        };
+        entries.push(AuditEntry {
+            path,
+            scope: Some(secret.scope.to_string()),
+            issues: vec![issue],
+        });
+    }
+
+    for (path, metadata) in found {
+        let mut issues = vec![];
+        let record = config.secrets.files.iter().find(|s| s.path() == path);
+        if record.is_none() && path.starts_with("secrets") {
+            issues.push(AuditIssue::Untracked);
+        }
+
+        let scope = record.map(|s| s.scope.clone()).or(scope_from_path(&path));
+        let expected = match &scope {
+            Some(scope) => {
+                let hosts = config.scope_hosts(scope);
+                if hosts.is_empty() {
+                    issues.push(AuditIssue::NoHosts);
+                }
+                for host in hosts.iter().filter(|h| h.age_key.is_none()) {
+                    issues.push(AuditIssue::NoHostKey {
+                        host: host.name.clone(),
+                    });
+                }
+                config.recipients(scope)
+            }
+            None => config.secrets.admins.values().cloned().collect(),
+        };
+
+        let current = metadata.recipients();
+        for recipient in expected.difference(&current) {
+            issues.push(AuditIssue::MissingRecipient {
+                recipient: recipient.clone(),
+                owner: owners[recipient].clone(),
+            });
+        }
+        for recipient in current.difference(&expected) {
+            issues.push(AuditIssue::ExtraRecipient {
+                recipient: recipient.clone(),
+                owner: owners.get(recipient).cloned(),
+            });
+        }
+
+        if !issues.is_empty() {
+            entries.push(AuditEntry {
+                path,
+                scope: scope.map(|s| s.to_string()),
+                issues,
+            });
+        }
+    }
+
+    entries.sort_by(|a, b| a.path.cmp(&b.path));
+    Ok(entries)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Ed25519 key from the seed 0x00..0x1f; the age recipient was derived independently.
+    const SSH_BLOB: &str = "AAAAC3NzaC1lZDI1NTE5AAAAIAOhB7/zzhC+HXDdGOdLwJln5NYwm6UNXx3chmQSVTG4";
+    const AGE_RECIPIENT: &str = "age1guqappygg504gkjqn76c4cl9skqu5s9v8al3z35ce4caatrnegqsayk6a2";
+
+    #[test]
+    fn converts_ssh_ed25519_to_age() {
+        assert_eq!(ssh_ed25519_to_age(SSH_BLOB).as_deref(), Some(AGE_RECIPIENT));
+        assert_eq!(ssh_ed25519_to_age("not base64!"), None);
+        assert_eq!(ssh_ed25519_to_age("AAAAB3NzaC1yc2EAAAADAQABAAAAgQC"), None);
+    }
+
+    #[test]
+    fn finds_recipient_in_keyscan_output() {
+        let keyscan = format!(
+            "# web.lan:22 SSH-2.0-OpenSSH_9.6\n\
+             web.lan ssh-rsa AAAAB3NzaC1yc2EAAAADAQABAAAAgQC\n\
+             # web.lan:22 SSH-2.0-OpenSSH_9.6\n\
+             web.lan ssh-ed25519 {SSH_BLOB}\n"
+        );
+        assert_eq!(find_ssh_recipient(keyscan).as_deref(), Some(AGE_RECIPIENT));
+        assert_eq!(find_ssh_recipient(format!("ssh-ed25519 {SSH_BLOB} root@web")).as_deref(), Some(AGE_RECIPIENT));
+        assert_eq!(find_ssh_recipient(format!("# ssh-ed25519 {SSH_BLOB}")), None);
+        assert_eq!(find_ssh_recipient("web.lan ssh-rsa AAAAB3NzaC1yc2EAAAADAQABAAAAgQC"), None);
+    }
+}